@@ -4,7 +4,7 @@ defmodule Typster do
 
   Typster is an Elixir wrapper for the Typst document preparation system,
   providing easy-to-use functions for compiling Typst templates with variable
-  binding, package support, and metadata injection.
+  binding, package support, and PDF metadata.
 
   ## Quick Start
 
@@ -32,9 +32,114 @@ defmodule Typster do
 
   All render functions accept an options keyword list:
   - `:variables` - Map of variables to bind into the template
+  - `:engine` - A `Typster.Engine` whose fonts and caches are reused instead
+    of being rebuilt for every render. The options fixed by the engine, such
+    as `:sandbox` and `:root_path`, return an error when passed along with it
+  - `:files` - Map of file paths to binaries (templates, images, data files)
+    served to the template instead of reading them from disk
   - `:package_paths` - List of local package directories
-  - `:metadata` - Map of PDF metadata (title, author, description, keywords, date)
+  - `:package_registry`, `:package_cache_dir` - Download packages from a
+    mirror or a `file://` directory, into a cache directory of your choice;
+    see `Typster.Engine.new/1`
+  - `:package_namespaces` - Serve private package namespaces from a
+    directory, archives on disk or an authenticated HTTP server; see
+    `Typster.Engine.new/1`
+  - `:package_hashes` - Pin downloaded package archives to SHA-256 digests;
+    see `Typster.Engine.new/1`
+  - `:offline` - Never download packages, only use the ones already on disk;
+    see `Typster.Engine.new/1`
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to make available
+  - `:font_paths` - List of directories with additional fonts
+  - `:ignore_system_fonts` - Don't use the fonts installed on the system
+  - `:sandbox` - Restrict file access to the root path (`:strict`) or to the
+    `:files` option (`:memory`); see `Typster.Engine.new/1`
+  - `:allowed_extensions`, `:max_file_size`, `:max_total_size` - Limit the
+    types and sizes of the files a template may load; see `Typster.Engine.new/1`
+  - `:pages` - Page numbers and ranges to include in the output, counting
+    from 1, e.g. `[1, 3..5]`; other pages are not exported or rasterized.
+    Pages are output in document order, so `5..3//-1` selects the same pages
+    as `3..5`, and ranges with a step such as `1..9//2` select each page in them.
+    Empty ranges such as `5..3//1` return an error
+  - `:metadata` - Map of PDF metadata; see "PDF Metadata" below
+  - `:pdf_standards` - PDF standards the output must conform to; see
+    "PDF Standards" below
+  - `:document_id`, `:creation_timestamp` - Stable PDF identifier and
+    creation date; see "Reproducible PDFs" below
   - `:pixel_per_pt` - PNG resolution (default: 2.0)
+  - `:sys_inputs` - Deliver `:variables` through `sys.inputs` instead of as
+    top-level names, like `typst compile --input key=value` (default: false)
+  - `:today` - A `Date`, `DateTime` or `NaiveDateTime` returned by
+    `datetime.today()` instead of the current date, for reproducible output
+  - `:with_warnings` - Return `{:ok, output, warnings}` with the compiler
+    warnings as `Typster.Diagnostic` structs (default: false)
+
+  ## PDF Metadata
+
+  The `:metadata` option sets the metadata of rendered PDFs:
+  - `:title` - Document title
+  - `:author` - Author name or list of author names
+  - `:description` - Document description (the PDF subject)
+  - `:keywords` - Comma-separated string or list of keywords
+  - `:date` - Creation date: a `Date`, `DateTime`, `NaiveDateTime`, an ISO 8601
+    string, `"none"` to omit it, or `"auto"` to use `:creation_timestamp`
+  - `:language` - Document language as a BCP 47 code, e.g. `"en"` or `"de-AT"`
+  - `:creator` - Application written as the PDF creator instead of "Typst"
+  - `:xmp` - Custom XMP properties: one or more `rdf:Description` elements
+    added to the XMP packet written for the other fields (e.g. the Factur-X
+    properties of an e-invoice). Custom namespaces in PDF/A documents also
+    need a PDF/A extension schema description.
+
+  Metadata given here takes precedence over the template's
+  `#set document(...)` and `#set text(lang: ...)` rules. Keys that are not
+  given or set to `nil` keep the values set by the template. Unknown keys and
+  values that aren't strings (or dates for `:date`) are rejected with an
+  error.
+
+  Templates see the metadata through `document.title`, `document.author`,
+  `#title()` and the other `document` fields in every output format, as if
+  they started with a `#set document(...)` rule. A template that sets a field
+  itself sees its own value, while the PDF still gets the option's value. The
+  language is only written to the PDF, so it doesn't change the layout.
+
+  ## PDF Standards
+
+  The `:pdf_standards` option takes a list of standards the PDF must conform
+  to, named like `typst compile --pdf-standard` names them, as strings or
+  atoms: `"1.4"` to `"2.0"` for the PDF version, `"a-1b"`, `"a-1a"`, `"a-2b"`,
+  `"a-2u"`, `"a-2a"`, `"a-3b"`, `"a-3u"`, `"a-3a"`, `"a-4"`, `"a-4f"` and
+  `"a-4e"` for PDF/A, and `"ua-1"` for PDF/UA-1 (e.g. `[:a_3b]` or
+  `["a-2a", "ua-1"]`).
+
+  Documents that don't meet a standard return a `Typster.CompileError` whose
+  diagnostics describe each violation, e.g. a missing document date for
+  PDF/A or a missing title for PDF/UA-1:
+
+      {:ok, pdf} =
+        Typster.render_pdf(template,
+          pdf_standards: [:a_3b],
+          metadata: %{title: "Invoice", date: Date.utc_today()}
+        )
+
+  ## Reproducible PDFs
+
+  Rendering the same template with the same inputs and fonts produces
+  byte-identical PDFs, so they can be deduplicated by hash or compared
+  against golden files. No creation date is written unless one is given, and
+  the PDF identifier is derived from the document's title and author.
+
+  - `:document_id` - A string that stably identifies the document across
+    renders, such as an invoice number. Its hash becomes the PDF's document
+    identifier, so revisions of the same document keep the same identifier.
+  - `:creation_timestamp` - A `Date`, `DateTime` or `NaiveDateTime` written
+    as the creation date when neither the `:date` metadata nor the template
+    sets one. DateTimes keep their UTC offset, NaiveDateTimes are taken as
+    UTC. Unlike `:today`, it doesn't change what `datetime.today()` returns.
+
+      {:ok, pdf} =
+        Typster.render_pdf(template,
+          document_id: "INV-2025-001",
+          creation_timestamp: ~U[2025-01-31 12:00:00Z]
+        )
 
   ## Concurrency
 
@@ -60,26 +165,54 @@ defmodule Typster do
   alias Typster.Native
 
   @type metadata :: %{
-          optional(:title) => String.t(),
-          optional(:author) => String.t(),
-          optional(:description) => String.t(),
-          optional(:keywords) => String.t(),
-          optional(:date) => String.t()
+          optional(:title) => String.t() | nil,
+          optional(:author) => String.t() | [String.t()] | nil,
+          optional(:description) => String.t() | nil,
+          optional(:keywords) => String.t() | [String.t()] | nil,
+          optional(:date) => String.t() | Date.t() | DateTime.t() | NaiveDateTime.t() | nil,
+          optional(:language) => String.t() | nil,
+          optional(:creator) => String.t() | nil,
+          optional(:xmp) => String.t() | nil
         }
+  @type files :: %{optional(String.t()) => binary()}
+  @type pages :: pos_integer() | Range.t() | [pos_integer() | Range.t()]
   @type package_paths :: [String.t()]
   @type root_path :: String.t()
   @type variables :: map()
   @type render_options :: [
+          engine: Typster.Engine.t(),
+          files: files(),
+          fonts: [binary()],
+          font_paths: [String.t()],
+          ignore_system_fonts: boolean(),
           metadata: metadata(),
+          pages: pages(),
+          pdf_standards: [String.t() | atom()],
+          document_id: String.t(),
+          creation_timestamp: Date.t() | DateTime.t() | NaiveDateTime.t(),
           package_paths: package_paths(),
+          package_cache_dir: String.t(),
+          package_registry: String.t(),
+          package_namespaces: %{optional(String.t() | atom()) => Typster.Engine.package_source()},
+          package_hashes: %{optional(String.t()) => String.t()},
+          offline: boolean(),
           pixel_per_pt: float(),
           root_path: root_path(),
-          variables: variables()
+          sandbox: :disabled | :strict | :memory,
+          allowed_extensions: [String.t()],
+          max_file_size: non_neg_integer(),
+          max_total_size: non_neg_integer(),
+          sys_inputs: boolean(),
+          today: Date.t() | DateTime.t() | NaiveDateTime.t(),
+          variables: variables(),
+          with_warnings: boolean()
         ]
 
   @type pdf_binary :: binary()
   @type svg_pages :: [String.t()]
   @type png_pages :: [binary()]
+  @type error_reason :: Typster.CompileError.t() | Typster.AccessError.t() | String.t()
+  @type warnings :: [Typster.Diagnostic.t()]
 
   ## Core API
 
@@ -91,16 +224,57 @@ defmodule Typster do
   - `opts` - Keyword list of options
 
   ## Options
-  - `:metadata` - Map of PDF metadata (default: %{})
+  - `:engine` - A `Typster.Engine` to reuse fonts and caches from; the engine
+    options (e.g. `:sandbox`, `:root_path`) can't be passed with it (default: nil)
+  - `:files` - Map of paths to binaries served instead of files on disk (default: %{})
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to make available (default: [])
+  - `:font_paths` - List of directories with additional fonts (default: [])
+  - `:ignore_system_fonts` - Don't use the fonts installed on the system (default: false)
+  - `:metadata` - Map of PDF metadata, see "PDF Metadata" in the module docs (default: %{})
+  - `:pdf_standards` - PDF/A, PDF/UA and PDF versions to conform to, see
+    "PDF Standards" in the module docs (default: [])
+  - `:document_id` - String identifying the document across renders, used for
+    the PDF's identifier (default: derived from the title and author)
+  - `:creation_timestamp` - Creation date written when the document doesn't
+    set one (default: no creation date)
   - `:package_paths` - List of local package directories (default: [])
+  - `:package_registry` - URL packages are downloaded from (default: the Typst registry)
+  - `:package_namespaces` - Sources of private package namespaces (default: %{})
+  - `:package_hashes` - SHA-256 digests package archives are pinned to (default: %{})
+  - `:offline` - Never download packages (default: false)
+  - `:pages` - Page numbers and ranges to include, e.g. `[1, 3..5]` (default: all pages)
   - `:root_path` - Root path for resolving relative imports (default: ".")
+  - `:sandbox` - `:disabled`, `:strict` or `:memory`, which also implies `:offline`
+    (default: :disabled)
+  - `:allowed_extensions` - File extensions templates may load (default: all)
+  - `:max_file_size` - Maximum size in bytes of a loaded file (default: unlimited)
+  - `:max_total_size` - Maximum size in bytes of all loaded files (default: unlimited)
+  - `:sys_inputs` - Expose variables as `sys.inputs` instead of top-level names (default: false)
+  - `:today` - Date returned by `datetime.today()` (default: the current date)
   - `:variables` - Map of variables to bind (default: %{})
+  - `:with_warnings` - Also return compiler warnings (default: false)
 
   ## Examples
 
       # Simple rendering
       {:ok, pdf} = Typster.render_pdf("= Hello World")
 
+      # Templates written for `typst compile --input year=2025`
+      template = "= Report for #sys.inputs.year"
+      {:ok, pdf} = Typster.render_pdf(template, variables: %{year: "2025"}, sys_inputs: true)
+
+      # With compiler warnings
+      {:ok, pdf, warnings} = Typster.render_pdf("= Hello World", with_warnings: true)
+
+      # With templates and assets that aren't on disk
+      template = ~s(#import "lib/header.typ": header\n#header\n#image("logo.png"\))
+      files = %{"lib/header.typ" => header_source, "logo.png" => logo_png}
+      {:ok, pdf} = Typster.render_pdf(template, files: files)
+
+      # Reusing fonts and caches across renders
+      {:ok, engine} = Typster.Engine.new()
+      {:ok, pdf} = Typster.render_pdf("= Hello World", engine: engine)
+
       # With variables
       template = "= Report for #year"
       {:ok, pdf} = Typster.render_pdf(template, variables: %{year: 2025})
@@ -118,23 +292,12 @@ defmodule Typster do
       {:ok, pdf} = Typster.render_pdf(template, package_paths: [])
   """
   @spec render_pdf(String.t(), render_options()) ::
-          {:ok, pdf_binary()} | {:error, String.t()}
+          {:ok, pdf_binary()} | {:ok, pdf_binary(), warnings()} | {:error, error_reason()}
   def render_pdf(source, opts \\ []) do
-    metadata = Keyword.get(opts, :metadata, %{}) |> stringify_keys()
-    package_paths = Keyword.get(opts, :package_paths, [])
-    root_path = Keyword.get(opts, :root_path, ".")
-    variables = Keyword.get(opts, :variables, %{}) |> stringify_keys()
-
-    options = %Native.TypsterOptions{
-      metadata: metadata,
-      package_paths: package_paths,
-      root_path: root_path,
-      variables: variables
-    }
-
-    case Native.compile_to_pdf(source, options) do
-      {:ok, pdf} -> {:ok, pdf}
-      {:error, reason} -> {:error, reason}
+    with :ok <- validate_options(opts) do
+      source
+      |> Native.compile_to_pdf(native_options(opts))
+      |> handle_result(opts)
     end
   end
 
@@ -148,9 +311,27 @@ defmodule Typster do
   - `opts` - Keyword list of options
 
   ## Options
+  - `:engine` - A `Typster.Engine` to reuse fonts and caches from; the engine
+    options (e.g. `:sandbox`, `:root_path`) can't be passed with it (default: nil)
+  - `:files` - Map of paths to binaries served instead of files on disk (default: %{})
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to make available (default: [])
+  - `:font_paths` - List of directories with additional fonts (default: [])
+  - `:ignore_system_fonts` - Don't use the fonts installed on the system (default: false)
   - `:package_paths` - List of local package directories (default: [])
+  - `:package_registry` - URL packages are downloaded from (default: the Typst registry)
+  - `:package_namespaces` - Sources of private package namespaces (default: %{})
+  - `:package_hashes` - SHA-256 digests package archives are pinned to (default: %{})
+  - `:offline` - Never download packages (default: false)
+  - `:pages` - Page numbers and ranges to render, e.g. `[1, 3..5]` (default: all pages)
   - `:root_path` - Root path for resolving relative imports (default: ".")
+  - `:sandbox` - `:disabled`, `:strict` or `:memory`, which also implies `:offline`
+    (default: :disabled)
+  - `:allowed_extensions` - File extensions templates may load (default: all)
+  - `:max_file_size` - Maximum size in bytes of a loaded file (default: unlimited)
+  - `:max_total_size` - Maximum size in bytes of all loaded files (default: unlimited)
+  - `:today` - Date returned by `datetime.today()` (default: the current date)
   - `:variables` - Map of variables to bind (default: %{})
+  - `:with_warnings` - Also return compiler warnings (default: false)
 
   ## Examples
 
@@ -160,24 +341,17 @@ defmodule Typster do
       # Multi-page document
       template = "= Page 1\\n#pagebreak()\\n= Page 2"
       {:ok, [svg1, svg2]} = Typster.render_svg(template)
+
+      # Only the second page
+      {:ok, [svg2]} = Typster.render_svg(template, pages: [2])
   """
   @spec render_svg(String.t(), render_options()) ::
-          {:ok, svg_pages()} | {:error, String.t()}
+          {:ok, svg_pages()} | {:ok, svg_pages(), warnings()} | {:error, error_reason()}
   def render_svg(source, opts \\ []) do
-    package_paths = Keyword.get(opts, :package_paths, [])
-    root_path = Keyword.get(opts, :root_path, ".")
-    variables = Keyword.get(opts, :variables, %{}) |> stringify_keys()
-
-    options = %Typster.Native.TypsterOptions{
-      metadata: %{},
-      package_paths: package_paths,
-      root_path: root_path,
-      variables: variables
-    }
-
-    case Native.compile_to_svg(source, options) do
-      {:ok, svg_pages} -> {:ok, svg_pages}
-      {:error, reason} -> {:error, reason}
+    with :ok <- validate_options(opts) do
+      source
+      |> Native.compile_to_svg(native_options(opts))
+      |> handle_result(opts)
     end
   end
 
@@ -191,10 +365,29 @@ defmodule Typster do
   - `opts` - Keyword list of options
 
   ## Options
+  - `:engine` - A `Typster.Engine` to reuse fonts and caches from; the engine
+    options (e.g. `:sandbox`, `:root_path`) can't be passed with it (default: nil)
+  - `:files` - Map of paths to binaries served instead of files on disk (default: %{})
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to make available (default: [])
+  - `:font_paths` - List of directories with additional fonts (default: [])
+  - `:ignore_system_fonts` - Don't use the fonts installed on the system (default: false)
   - `:package_paths` - List of local package directories (default: [])
+  - `:package_registry` - URL packages are downloaded from (default: the Typst registry)
+  - `:package_namespaces` - Sources of private package namespaces (default: %{})
+  - `:package_hashes` - SHA-256 digests package archives are pinned to (default: %{})
+  - `:offline` - Never download packages (default: false)
+  - `:pages` - Page numbers and ranges to render, e.g. `1` for a thumbnail of the first
+    page (default: all pages)
   - `:pixel_per_pt` - Resolution in pixels per point (default: 2.0, higher = better quality)
   - `:root_path` - Root path for resolving relative imports (default: ".")
+  - `:sandbox` - `:disabled`, `:strict` or `:memory`, which also implies `:offline`
+    (default: :disabled)
+  - `:allowed_extensions` - File extensions templates may load (default: all)
+  - `:max_file_size` - Maximum size in bytes of a loaded file (default: unlimited)
+  - `:max_total_size` - Maximum size in bytes of all loaded files (default: unlimited)
+  - `:today` - Date returned by `datetime.today()` (default: the current date)
   - `:variables` - Map of variables to bind (default: %{})
+  - `:with_warnings` - Also return compiler warnings (default: false)
 
   ## Examples
 
@@ -208,24 +401,12 @@ defmodule Typster do
       {:ok, [png1, png2]} = Typster.render_png(template)
   """
   @spec render_png(String.t(), render_options()) ::
-          {:ok, png_pages()} | {:error, String.t()}
+          {:ok, png_pages()} | {:ok, png_pages(), warnings()} | {:error, error_reason()}
   def render_png(source, opts \\ []) do
-    package_paths = Keyword.get(opts, :package_paths, [])
-    pixel_per_pt = Keyword.get(opts, :pixel_per_pt, 2.0)
-    root_path = Keyword.get(opts, :root_path, ".")
-    variables = Keyword.get(opts, :variables, %{}) |> stringify_keys()
-
-    options = %Typster.Native.TypsterOptions{
-      metadata: %{},
-      package_paths: package_paths,
-      pixel_per_pt: pixel_per_pt,
-      root_path: root_path,
-      variables: variables
-    }
-
-    case Native.compile_to_png(source, options) do
-      {:ok, png_pages} -> {:ok, png_pages}
-      {:error, reason} -> {:error, reason}
+    with :ok <- validate_options(opts) do
+      source
+      |> Native.compile_to_png(native_options(opts))
+      |> handle_result(opts)
     end
   end
 
@@ -249,9 +430,10 @@ defmodule Typster do
       Typster.render_to_file(template, "output.png", pixel_per_pt: 4.0)
   """
   @spec render_to_file(String.t(), String.t(), render_options()) ::
-          :ok | {:error, String.t()}
+          :ok | {:error, error_reason()}
   def render_to_file(source, output_path, opts \\ []) do
     extension = Path.extname(output_path) |> String.downcase()
+    opts = Keyword.delete(opts, :with_warnings)
 
     case extension do
       ".pdf" ->
@@ -286,13 +468,32 @@ defmodule Typster do
   - `opts` - Keyword list of options
 
   ## Options
+  - `:engine` - A `Typster.Engine` to reuse fonts and caches from; the engine
+    options (e.g. `:sandbox`, `:root_path`) can't be passed with it (default: nil)
+  - `:files` - Map of paths to binaries served instead of files on disk (default: %{})
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to make available (default: [])
+  - `:font_paths` - List of directories with additional fonts (default: [])
+  - `:ignore_system_fonts` - Don't use the fonts installed on the system (default: false)
   - `:package_paths` - List of local package directories (default: [])
+  - `:package_registry` - URL packages are downloaded from (default: the Typst registry)
+  - `:package_namespaces` - Sources of private package namespaces (default: %{})
+  - `:package_hashes` - SHA-256 digests package archives are pinned to (default: %{})
+  - `:offline` - Never download packages (default: false)
   - `:root_path` - Root path for resolving relative imports (default: ".")
+  - `:sandbox` - `:disabled`, `:strict` or `:memory`, which also implies `:offline`
+    (default: :disabled)
+  - `:allowed_extensions` - File extensions templates may load (default: all)
+  - `:max_file_size` - Maximum size in bytes of a loaded file (default: unlimited)
+  - `:max_total_size` - Maximum size in bytes of all loaded files (default: unlimited)
+  - `:today` - Date returned by `datetime.today()` (default: the current date)
   - `:variables` - Map of variables to bind (default: %{})
+  - `:with_warnings` - Return `{:ok, warnings}` instead of `:ok` (default: false)
 
   ## Returns
   - `:ok` if the template syntax is valid
-  - `{:error, errors}` where errors is a list of error messages
+  - `{:ok, warnings}` if the template is valid and `:with_warnings` is set
+  - `{:error, diagnostics}` where diagnostics is a list of `Typster.Diagnostic`
+    structs containing the errors followed by any warnings
 
   ## Examples
 
@@ -301,7 +502,7 @@ defmodule Typster do
 
       # Invalid template
       {:error, errors} = Typster.check("= Unclosed #for")
-      # errors will contain a list of error messages
+      # errors is a list of %Typster.Diagnostic{} with line/column information
 
       # With variables
       template = "= Report for #year"
@@ -311,23 +512,19 @@ defmodule Typster do
       template = ~S(#import "@preview/tiaoma:0.3.0": qrcode)
       :ok = Typster.check(template, %{}, package_paths: [])
   """
-  @spec check(String.t(), render_options()) :: :ok | {:error, [String.t()]}
+  @spec check(String.t(), render_options()) ::
+          :ok | {:ok, warnings()} | {:error, [Typster.Diagnostic.t()]}
   def check(source, opts \\ []) do
-    package_paths = Keyword.get(opts, :package_paths, [])
-    root_path = Keyword.get(opts, :root_path, ".")
-    variables = Keyword.get(opts, :variables, %{}) |> stringify_keys()
-
-    options = %Typster.Native.TypsterOptions{
-      metadata: %{},
-      package_paths: package_paths,
-      root_path: root_path,
-      variables: variables
-    }
-
-    case Native.check_syntax(source, options) do
-      {:ok, []} -> :ok
-      {:ok, errors} -> {:error, errors}
-      {:error, reason} -> {:error, [reason]}
+    with :ok <- validate_options(opts),
+         {:ok, diagnostics} <- Native.check_syntax(source, native_options(opts)) do
+      cond do
+        Enum.any?(diagnostics, &(&1.severity == :error)) -> {:error, diagnostics}
+        Keyword.get(opts, :with_warnings, false) -> {:ok, diagnostics}
+        true -> :ok
+      end
+    else
+      {:error, reason} ->
+        {:error, [%Typster.Diagnostic{severity: :error, message: reason}]}
     end
   end
 
@@ -349,14 +546,60 @@ defmodule Typster do
           IO.puts("Syntax error: \#{e.message}")
       end
   """
-  @spec check!(String.t(), render_options()) :: :ok
+  @spec check!(String.t(), render_options()) :: :ok | {:ok, warnings()}
   def check!(source, opts \\ []) do
     case check(source, opts) do
       :ok -> :ok
-      {:error, errors} -> raise Typster.CompileError, message: Enum.join(errors, "\n")
+      {:ok, warnings} -> {:ok, warnings}
+      {:error, errors} -> raise Typster.CompileError, diagnostics: errors
     end
   end
 
+  @doc """
+  List the fonts available to documents.
+
+  Returns the fonts in the order Typst prefers them when several fonts match
+  the same family and variant. Useful for building font pickers and for
+  debugging "unknown font family" warnings.
+
+  ## Options
+  - `:engine` - List the fonts of a `Typster.Engine` (default: nil)
+  - `:fonts` - List of font binaries (TTF, OTF or TTC) to include (default: [])
+  - `:font_paths` - List of directories with additional fonts (default: [])
+  - `:ignore_system_fonts` - Leave out the fonts installed on the system (default: false)
+
+  ## Examples
+
+      {:ok, fonts} = Typster.list_fonts()
+      Enum.find(fonts, &(&1.family == "Libertinus Serif"))
+      #=> %Typster.Font{family: "Libertinus Serif", style: :normal, weight: 400, source: :embedded, ...}
+
+      {:ok, fonts} = Typster.list_fonts(engine: engine)
+  """
+  @spec list_fonts(render_options()) :: {:ok, [Typster.Font.t()]} | {:error, String.t()}
+  def list_fonts(opts \\ []) do
+    with :ok <- Typster.Engine.validate_render_options(opts) do
+      Native.list_fonts(Keyword.get(opts, :engine), Typster.Engine.native_options(opts))
+    end
+  end
+
+  @doc """
+  Rescan the system fonts.
+
+  System fonts are scanned once, on the first render, and shared by all
+  renders afterwards. Call this after installing or removing fonts to make
+  the changes visible to subsequent renders and to engines created from now
+  on. Existing `Typster.Engine`s keep the fonts they were created with.
+
+  ## Examples
+
+      :ok = Typster.refresh_fonts()
+  """
+  @spec refresh_fonts() :: :ok
+  def refresh_fonts do
+    Native.refresh_fonts()
+  end
+
   @doc """
   Render a Typst template to PDF format, raising on error.
 
@@ -367,11 +610,12 @@ defmodule Typster do
       pdf = Typster.render_pdf!(template)
       pdf = Typster.render_pdf!(template, %{year: 2025})
   """
-  @spec render_pdf!(String.t(), render_options()) :: pdf_binary()
+  @spec render_pdf!(String.t(), render_options()) :: pdf_binary() | {pdf_binary(), warnings()}
   def render_pdf!(source, opts \\ []) do
     case render_pdf(source, opts) do
       {:ok, pdf} -> pdf
-      {:error, reason} -> raise Typster.CompileError, message: reason
+      {:ok, pdf, warnings} -> {pdf, warnings}
+      {:error, reason} -> raise_compile_error(reason)
     end
   end
 
@@ -380,11 +624,12 @@ defmodule Typster do
 
   Same as `render_svg/3` but raises `Typster.CompileError` on failure.
   """
-  @spec render_svg!(String.t(), render_options()) :: svg_pages()
+  @spec render_svg!(String.t(), render_options()) :: svg_pages() | {svg_pages(), warnings()}
   def render_svg!(source, opts \\ []) do
     case render_svg(source, opts) do
       {:ok, svg_pages} -> svg_pages
-      {:error, reason} -> raise Typster.CompileError, message: reason
+      {:ok, svg_pages, warnings} -> {svg_pages, warnings}
+      {:error, reason} -> raise_compile_error(reason)
     end
   end
 
@@ -393,11 +638,12 @@ defmodule Typster do
 
   Same as `render_png/3` but raises `Typster.CompileError` on failure.
   """
-  @spec render_png!(String.t(), render_options()) :: png_pages()
+  @spec render_png!(String.t(), render_options()) :: png_pages() | {png_pages(), warnings()}
   def render_png!(source, opts \\ []) do
     case render_png(source, opts) do
       {:ok, png_pages} -> png_pages
-      {:error, reason} -> raise Typster.CompileError, message: reason
+      {:ok, png_pages, warnings} -> {png_pages, warnings}
+      {:error, reason} -> raise_compile_error(reason)
     end
   end
 
@@ -410,12 +656,105 @@ defmodule Typster do
   def render_to_file!(source, output_path, opts \\ []) do
     case render_to_file(source, output_path, opts) do
       :ok -> :ok
-      {:error, reason} -> raise Typster.CompileError, message: reason
+      {:error, reason} -> raise_compile_error(reason)
     end
   end
 
   ## Private Helpers
 
+  # Build the options struct passed to the NIFs
+  defp native_options(opts) do
+    %Native.TypsterOptions{
+      creation_timestamp: Keyword.get(opts, :creation_timestamp) |> datetime_option(),
+      document_id: Keyword.get(opts, :document_id),
+      engine: Keyword.get(opts, :engine),
+      engine_options: Typster.Engine.native_options(opts),
+      files: Keyword.get(opts, :files, %{}) |> Map.new(fn {path, data} -> {"#{path}", data} end),
+      metadata: Keyword.get(opts, :metadata, %{}) |> metadata_options(),
+      pages: Keyword.get(opts, :pages) |> page_ranges(),
+      pdf_standards: Keyword.get(opts, :pdf_standards, []) |> Enum.map(&to_string/1),
+      pixel_per_pt: Keyword.get(opts, :pixel_per_pt, 2.0),
+      sys_inputs: Keyword.get(opts, :sys_inputs, false),
+      today: Keyword.get(opts, :today) |> datetime_option(),
+      variables: Keyword.get(opts, :variables, %{}) |> stringify_keys()
+    }
+  end
+
+  # Dates are passed to the NIFs as maps with string keys, like variables
+  defp datetime_option(%_{} = datetime), do: stringify_keys(datetime)
+  defp datetime_option(value), do: value
+
+  # Options are checked before rendering when the NIFs can't tell what's wrong
+  defp validate_options(opts) do
+    with :ok <- Typster.Engine.validate_render_options(opts) do
+      validate_pages(Keyword.get(opts, :pages))
+    end
+  end
+
+  # An empty range like `5..3//1` selects no pages, and is most likely meant
+  # to be descending
+  defp validate_pages(pages) do
+    case pages |> List.wrap() |> Enum.find(&(is_struct(&1, Range) and Range.size(&1) == 0)) do
+      nil -> :ok
+      range -> {:error, "Empty page range #{inspect(range)} selects no pages"}
+    end
+  end
+
+  # Page ranges are passed to the NIFs as `{first, last}` tuples. Descending
+  # ranges select the same pages as ascending ones, and ranges with a step
+  # select each page they contain.
+  defp page_ranges(nil), do: nil
+
+  defp page_ranges(pages) do
+    pages
+    |> List.wrap()
+    |> Enum.flat_map(fn
+      first..last//1 -> [{first, last}]
+      first..last//-1 -> [{last, first}]
+      %Range{} = range -> Enum.to_list(range)
+      page -> [page]
+    end)
+  end
+
+  # Metadata is passed to the NIFs as strings, with dates in ISO 8601 format.
+  # Keys set to `nil` are left out, so they keep the template's values.
+  defp metadata_options(metadata) do
+    for {key, value} <- metadata, value != nil, into: %{} do
+      {to_string(key), metadata_value(value)}
+    end
+  end
+
+  defp metadata_value(%Date{} = date), do: Date.to_iso8601(date)
+  defp metadata_value(%DateTime{} = datetime), do: DateTime.to_iso8601(datetime)
+  defp metadata_value(%NaiveDateTime{} = datetime), do: NaiveDateTime.to_iso8601(datetime)
+  defp metadata_value(value), do: value
+
+  # The rendering NIFs return the output together with the compiler warnings,
+  # which are only passed on when the caller asked for them
+  defp handle_result({:ok, {output, warnings}}, opts) do
+    if Keyword.get(opts, :with_warnings, false) do
+      {:ok, output, warnings}
+    else
+      {:ok, output}
+    end
+  end
+
+  defp handle_result({:error, reason}, _opts), do: {:error, to_error(reason)}
+
+  # The NIFs return a list of diagnostics when the Typst compiler reports
+  # errors, the details of the denied read when the sandbox refused a file,
+  # and a plain message for everything else (e.g. invalid variables)
+  defp to_error(diagnostics) when is_list(diagnostics),
+    do: Typster.CompileError.exception(diagnostics: diagnostics)
+
+  defp to_error({:access_denied, details}), do: struct!(Typster.AccessError, details)
+
+  defp to_error(reason), do: reason
+
+  defp raise_compile_error(%Typster.CompileError{} = error), do: raise(error)
+  defp raise_compile_error(%Typster.AccessError{} = error), do: raise(error)
+  defp raise_compile_error(reason), do: raise(Typster.CompileError, message: reason)
+
   # Convert map with atom keys to string keys for NIF compatibility
   # Recursively handle nested maps and lists
   # NOTE: Only keys are converted to strings, values are preserved as-is
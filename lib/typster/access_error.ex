@@ -0,0 +1,51 @@
+defmodule Typster.AccessError do
+  @moduledoc """
+  Error returned when a template tries to read a file the `:sandbox` option
+  or the file limits (`:allowed_extensions`, `:max_file_size`,
+  `:max_total_size`) do not allow.
+
+  The non-bang rendering functions return it as the error reason, and the
+  bang versions raise it.
+
+  ## Fields
+
+  - `:message` - Human readable description of the denied access
+  - `:path` - The denied path as requested by the template, relative to the
+    project root (e.g. `"/secrets/key.pem"`)
+  - `:reason` - Why the read was refused:
+    - `:outside_root` - The file resolves to a location outside the root
+      path (e.g. through a symlink) in `:strict` mode
+    - `:disk_access_disabled` - The file is not in `:files` in `:memory` mode
+    - `:extension_not_allowed` - The file extension is not in
+      `:allowed_extensions`
+    - `:file_too_large` - The file is larger than `:max_file_size`
+    - `:total_size_exceeded` - Loading the file would exceed `:max_total_size`
+  - `:diagnostics` - The `Typster.Diagnostic`s reported by the compiler for
+    the failed compilation
+
+  ## Examples
+
+      {:error, %Typster.AccessError{path: "/etc/passwd", reason: :disk_access_disabled}} =
+        Typster.render_pdf(~s(#read("/etc/passwd"\)), sandbox: :memory)
+  """
+
+  defexception [:message, :path, :reason, diagnostics: []]
+
+  @type reason ::
+          :outside_root
+          | :disk_access_disabled
+          | :extension_not_allowed
+          | :file_too_large
+          | :total_size_exceeded
+
+  @type t :: %__MODULE__{
+          message: String.t(),
+          path: String.t(),
+          reason: reason(),
+          diagnostics: [Typster.Diagnostic.t()]
+        }
+
+  defimpl String.Chars do
+    def to_string(error), do: error.message
+  end
+end
@@ -3,7 +3,12 @@ defmodule Typster.CompileError do
   Exception raised when Typst compilation fails.
 
   This exception is raised by the bang (!) versions of rendering functions
-  when compilation or rendering fails.
+  when compilation or rendering fails, and returned as the error reason by
+  the non-bang versions when the Typst compiler reports errors.
+
+  The `:diagnostics` field holds the structured `Typster.Diagnostic`s
+  reported by the compiler (empty for errors that did not come from Typst,
+  such as invalid variables).
 
   ## Examples
 
@@ -13,17 +18,44 @@ defmodule Typster.CompileError do
         e in Typster.CompileError ->
           IO.puts("Compilation failed: " <> e.message)
       end
+
+      {:error, %Typster.CompileError{diagnostics: diagnostics}} =
+        Typster.render_pdf("#unknown")
   """
 
-  defexception [:message]
+  defexception [:message, diagnostics: []]
+
+  @type t :: %__MODULE__{
+          message: String.t(),
+          diagnostics: [Typster.Diagnostic.t()]
+        }
 
   @impl true
   def exception(opts) when is_list(opts) do
-    message = Keyword.get(opts, :message, "Typst compilation failed")
-    %__MODULE__{message: message}
+    diagnostics = Keyword.get(opts, :diagnostics, [])
+
+    message =
+      Keyword.get_lazy(opts, :message, fn ->
+        if diagnostics == [] do
+          "Typst compilation failed"
+        else
+          format_diagnostics(diagnostics)
+        end
+      end)
+
+    %__MODULE__{message: message, diagnostics: diagnostics}
   end
 
   def exception(message) when is_binary(message) do
     %__MODULE__{message: message}
   end
+
+  defp format_diagnostics(diagnostics) do
+    details = Enum.map_join(diagnostics, "\n", &Typster.Diagnostic.format/1)
+    "Compilation failed:\n" <> details
+  end
+
+  defimpl String.Chars do
+    def to_string(error), do: error.message
+  end
 end
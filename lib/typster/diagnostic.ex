@@ -0,0 +1,102 @@
+defmodule Typster.Diagnostic do
+  @moduledoc """
+  A structured error or warning reported by the Typst compiler.
+
+  Locations are resolved to 1-based line and column numbers (columns count
+  characters, not bytes) so they can be used to point template authors at the
+  exact place that caused the problem.
+
+  ## Fields
+
+  - `:severity` - `:error` or `:warning`
+  - `:message` - The diagnostic message
+  - `:path` - File the diagnostic points into (`"main.typ"` for the template
+    itself, `"@preview/name:1.0.0/lib.typ"` for package files), or `nil`
+  - `:line`, `:column` - Start of the offending span, or `nil` if unknown
+  - `:end_line`, `:end_column` - End of the offending span, or `nil` if unknown
+  - `:snippet` - The source text covered by the span, or `nil`
+  - `:hints` - List of hint strings suggesting how to fix the problem
+  - `:trace` - List of trace points (function calls, imports, show rules)
+    that led to the diagnostic, innermost first
+
+  ## Examples
+
+      {:error, [diagnostic]} = Typster.check("= Hello #unknown")
+      diagnostic.message
+      #=> "unknown variable: unknown"
+      {diagnostic.line, diagnostic.column}
+      #=> {1, 10}
+  """
+
+  @type severity :: :error | :warning
+
+  @type trace_point :: %{
+          message: String.t(),
+          path: String.t() | nil,
+          line: pos_integer() | nil,
+          column: pos_integer() | nil,
+          end_line: pos_integer() | nil,
+          end_column: pos_integer() | nil
+        }
+
+  @type t :: %__MODULE__{
+          severity: severity(),
+          message: String.t(),
+          path: String.t() | nil,
+          line: pos_integer() | nil,
+          column: pos_integer() | nil,
+          end_line: pos_integer() | nil,
+          end_column: pos_integer() | nil,
+          snippet: String.t() | nil,
+          hints: [String.t()],
+          trace: [trace_point()]
+        }
+
+  defstruct severity: :error,
+            message: "",
+            path: nil,
+            line: nil,
+            column: nil,
+            end_line: nil,
+            end_column: nil,
+            snippet: nil,
+            hints: [],
+            trace: []
+
+  @doc """
+  Format a diagnostic as a single human-readable line (plus hints).
+
+  ## Examples
+
+      iex> Typster.Diagnostic.format(%Typster.Diagnostic{
+      ...>   severity: :error,
+      ...>   message: "unknown variable: foo",
+      ...>   path: "main.typ",
+      ...>   line: 2,
+      ...>   column: 3
+      ...> })
+      "main.typ:2:3: error: unknown variable: foo"
+
+      iex> Typster.Diagnostic.format(%Typster.Diagnostic{
+      ...>   severity: :warning,
+      ...>   message: "unknown font family: foo",
+      ...>   hints: ["check the spelling"]
+      ...> })
+      "warning: unknown font family: foo\\n  hint: check the spelling"
+  """
+  @spec format(t()) :: String.t()
+  def format(%__MODULE__{} = diagnostic) do
+    header = "#{location(diagnostic)}#{diagnostic.severity}: #{diagnostic.message}"
+
+    Enum.reduce(diagnostic.hints, header, fn hint, acc -> acc <> "\n  hint: " <> hint end)
+  end
+
+  defp location(%{path: nil}), do: ""
+  defp location(%{path: path, line: nil}), do: "#{path}: "
+  defp location(%{path: path, line: line, column: nil}), do: "#{path}:#{line}: "
+  defp location(%{path: path, line: line, column: column}), do: "#{path}:#{line}:#{column}: "
+
+  defimpl String.Chars do
+    def to_string(diagnostic), do: Typster.Diagnostic.format(diagnostic)
+  end
+end
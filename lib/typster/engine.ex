@@ -0,0 +1,273 @@
+defmodule Typster.Engine do
+  @moduledoc """
+  A reusable rendering engine holding fonts, the Typst standard library,
+  package configuration and a file cache.
+
+  Every render without an engine builds the standard library and reads its
+  files from scratch. Create an engine once (e.g. at application start) and
+  pass it to the render functions with the `:engine` option to reuse them.
+  System fonts are scanned once per OS process and shared by all engines (see
+  `Typster.refresh_fonts/0`).
+
+  Engines are safe to share between processes. Files read from disk (imported
+  templates, images, package files) are cached in the engine and re-read when
+  their modification time or size changes. Imported templates are kept
+  parsed, and only the parts that changed are reparsed after an edit.
+
+  Cached files and parsed templates that weren't used by the last 10 renders
+  with the engine are dropped after each render, so an engine's memory use
+  follows the documents it currently renders rather than growing with every
+  file it has ever loaded. The compiler's memoized results are shared by the
+  whole OS process instead: after each render, with or without an engine,
+  the results that weren't used by the last 10 renders in the process are
+  dropped.
+
+  ## Examples
+
+      {:ok, engine} = Typster.Engine.new(root_path: "priv/templates")
+
+      {:ok, pdf} = Typster.render_pdf(template, engine: engine, variables: %{id: 1})
+      {:ok, pdf} = Typster.render_pdf(template, engine: engine, variables: %{id: 2})
+  """
+
+  alias Typster.Native
+
+  # The options fixed when an engine is created
+  @engine_options [
+    :fonts,
+    :font_paths,
+    :ignore_system_fonts,
+    :package_paths,
+    :package_cache_dir,
+    :package_registry,
+    :package_namespaces,
+    :package_hashes,
+    :root_path,
+    :sandbox,
+    :allowed_extensions,
+    :max_file_size,
+    :max_total_size,
+    :offline
+  ]
+
+  @opaque t :: reference()
+
+  @type package_source ::
+          {:directory, String.t()}
+          | {:archives, String.t()}
+          | {:http, String.t()}
+          | {:http, String.t(), [headers: %{optional(String.t()) => String.t()}]}
+
+  @type option ::
+          {:fonts, [binary()]}
+          | {:font_paths, [String.t()]}
+          | {:ignore_system_fonts, boolean()}
+          | {:package_paths, [String.t()]}
+          | {:package_cache_dir, String.t()}
+          | {:package_registry, String.t()}
+          | {:package_namespaces, %{optional(String.t() | atom()) => package_source()}}
+          | {:package_hashes, %{optional(String.t()) => String.t()}}
+          | {:root_path, String.t()}
+          | {:sandbox, :disabled | :strict | :memory}
+          | {:allowed_extensions, [String.t()]}
+          | {:max_file_size, non_neg_integer()}
+          | {:max_total_size, non_neg_integer()}
+          | {:offline, boolean()}
+
+  @doc """
+  Create a new engine.
+
+  The fonts, package settings, root path, sandbox and file limits are fixed
+  when the engine is created. Passing any of these options to a render
+  together with the engine returns an error instead of overriding them.
+
+  ## Options
+  - `:fonts` - List of font files (TTF, OTF or TTC) as binaries, which take
+    precedence over all other fonts (default: [])
+  - `:font_paths` - List of directories searched recursively for fonts, which
+    take precedence over system and embedded fonts (default: [])
+  - `:ignore_system_fonts` - Only use the fonts from `:fonts`, `:font_paths`
+    and the fonts embedded in Typster (default: false)
+  - `:package_paths` - List of local package directories (default: [])
+  - `:package_registry` - URL of the registry packages are downloaded from
+    (default: `"https://packages.typst.org"`). Archives are fetched as
+    `{namespace}/{name}-{version}.tar.gz` below it, so it can be an HTTP(S)
+    mirror of the official registry or a `file://` URL of a directory laid
+    out the same way.
+  - `:package_namespaces` - Map of namespaces to the source their packages
+    come from, for private packages that aren't in the registry
+    (default: %{}). Each source is one of:
+    - `{:directory, path}` - Unpacked packages laid out as
+      `{name}/{version}`, used in place
+    - `{:archives, path}` - A directory of `{name}-{version}.tar.gz`
+      archives, extracted into the package cache
+    - `{:http, url}` or `{:http, url, headers: headers}` - Archives
+      downloaded from `{url}/{name}-{version}.tar.gz`, sending the given
+      headers (e.g. `%{"authorization" => "Bearer ..."}`) with the request
+
+    Packages missing from a namespace's source are not looked up in the
+    registry. Sources of any other shape return an error.
+  - `:package_hashes` - Map of package specs to the SHA-256 digest of their
+    archive, e.g. `%{"@preview/tiaoma:0.3.0" => "sha256:9f86d0..."}`
+    (default: %{}). Downloaded archives of pinned packages are checked before
+    they are extracted, and a mismatch fails to compile with a diagnostic
+    naming the expected and actual digests. Pinned packages are only taken
+    from the cache if Typster verified them when installing them; otherwise
+    they are downloaded and verified again, or fail to compile when
+    `:offline`. Packages served from a `:directory` source are not checked.
+  - `:package_cache_dir` - Directory downloaded packages are extracted to
+    (default: the Typst CLI's cache, e.g. `~/.cache/typst/packages`)
+  - `:offline` - Never download packages. Packages that are neither in
+    `:package_paths` nor in the package cache fail to compile with a
+    "package @preview/name:version not available offline" diagnostic instead
+    (default: false)
+  - `:root_path` - Root path for resolving relative imports (default: ".")
+  - `:sandbox` - Restrict which project files templates may read
+    (default: `:disabled`):
+    - `:disabled` - Files are read from the root path, following symlinks
+      wherever they lead
+    - `:strict` - Files must be inside the root path after resolving
+      symlinks
+    - `:memory` - Only files passed with the `:files` option are served;
+      the disk is never read. This also implies `offline: true`, so
+      packages are never downloaded
+
+    Package files are not affected by the sandbox.
+  - `:allowed_extensions` - List of file extensions templates may load, such
+    as `["typ", "png", "json"]`; other files are refused (default: all)
+  - `:max_file_size` - Maximum size in bytes of a single file a template
+    loads (default: unlimited)
+  - `:max_total_size` - Maximum size in bytes of all files loaded by one
+    render (default: unlimited)
+
+  The size limits apply to every file a template loads: files passed with
+  `:files`, files on disk and package files. `:allowed_extensions` only
+  applies to project files, since packages load their own `typst.toml` and
+  assets. The template itself is not counted.
+
+  ## Examples
+
+      {:ok, engine} = Typster.Engine.new()
+      {:ok, engine} = Typster.Engine.new(package_paths: ["priv/typst/packages"])
+
+      # Packages from an internal mirror, or from archives on disk
+      {:ok, engine} = Typster.Engine.new(package_registry: "https://typst-mirror.internal")
+      {:ok, engine} = Typster.Engine.new(package_registry: "file:///srv/typst-packages")
+
+      # Private packages imported as `@acme/letterhead:1.0.0`
+      {:ok, engine} =
+        Typster.Engine.new(
+          package_namespaces: %{
+            "acme" =>
+              {:http, "https://typst.acme.internal/acme", headers: %{"authorization" => token}},
+            "local" => {:directory, "priv/typst/local"}
+          }
+        )
+
+      # Hosts without network access: only use packages already on disk
+      {:ok, engine} = Typster.Engine.new(package_paths: ["priv/typst/packages"], offline: true)
+
+      # Render the same way on every machine, regardless of installed fonts
+      {:ok, engine} = Typster.Engine.new(font_paths: ["priv/fonts"], ignore_system_fonts: true)
+
+      # Licensed fonts loaded from a database or object storage
+      {:ok, engine} = Typster.Engine.new(fonts: [MyApp.Assets.fetch_font!("brand.otf")])
+  """
+  @spec new([option()]) :: {:ok, t()} | {:error, String.t()}
+  def new(opts \\ []) do
+    with :ok <- validate_package_namespaces(Keyword.get(opts, :package_namespaces, %{})) do
+      opts
+      |> native_options()
+      |> Native.new_engine()
+    end
+  end
+
+  # Renders with an `:engine` use its settings. Passing the same options to
+  # the render is refused rather than ignored, since they include the sandbox.
+  @doc false
+  def validate_render_options(opts) do
+    given =
+      if Keyword.get(opts, :engine),
+        do: Enum.filter(@engine_options, &Keyword.has_key?(opts, &1)),
+        else: []
+
+    case given do
+      [] ->
+        validate_package_namespaces(Keyword.get(opts, :package_namespaces, %{}))
+
+      keys ->
+        {:error,
+         "#{Enum.map_join(keys, ", ", &inspect/1)} can't be combined with :engine, " <>
+           "pass them to Typster.Engine.new/1 instead"}
+    end
+  end
+
+  # Also used to build a throwaway engine for renders without an `:engine`
+  @doc false
+  def native_options(opts) do
+    %Native.EngineOptions{
+      fonts: Keyword.get(opts, :fonts, []),
+      font_paths: Keyword.get(opts, :font_paths, []),
+      ignore_system_fonts: Keyword.get(opts, :ignore_system_fonts, false),
+      package_paths: Keyword.get(opts, :package_paths, []),
+      package_cache_dir: Keyword.get(opts, :package_cache_dir),
+      package_registry: Keyword.get(opts, :package_registry),
+      package_namespaces: package_namespaces(Keyword.get(opts, :package_namespaces, %{})),
+      package_hashes: Keyword.get(opts, :package_hashes, %{}),
+      root_path: Keyword.get(opts, :root_path, "."),
+      sandbox: Keyword.get(opts, :sandbox, :disabled),
+      allowed_extensions: Keyword.get(opts, :allowed_extensions),
+      max_file_size: Keyword.get(opts, :max_file_size),
+      max_total_size: Keyword.get(opts, :max_total_size),
+      offline: Keyword.get(opts, :offline, false)
+    }
+  end
+
+  # Malformed package sources are reported here, since the NIFs can only
+  # refuse to decode them
+  defp validate_package_namespaces(namespaces) when is_map(namespaces) do
+    Enum.find_value(namespaces, :ok, fn {namespace, source} ->
+      if not valid_package_source?(source) do
+        {:error,
+         "Invalid package source #{inspect(source)} for namespace #{inspect(namespace)}, " <>
+           "expected {:directory, path}, {:archives, path}, {:http, url} or " <>
+           "{:http, url, headers: %{name => value}}"}
+      end
+    end)
+  end
+
+  defp validate_package_namespaces(namespaces),
+    do: {:error, ":package_namespaces must be a map, got: #{inspect(namespaces)}"}
+
+  defp valid_package_source?({kind, path}) when kind in [:directory, :archives, :http],
+    do: is_binary(path)
+
+  defp valid_package_source?({:http, url, opts}) when is_binary(url) and is_list(opts) do
+    case Keyword.keyword?(opts) and Keyword.get(opts, :headers, %{}) do
+      headers when is_map(headers) ->
+        Enum.all?(headers, fn {name, value} ->
+          (is_binary(name) or is_atom(name)) and is_binary(value)
+        end)
+
+      _ ->
+        false
+    end
+  end
+
+  defp valid_package_source?(_source), do: false
+
+  defp package_namespaces(namespaces) do
+    Map.new(namespaces, fn {namespace, source} ->
+      {namespace |> to_string() |> String.trim_leading("@"), package_source(source)}
+    end)
+  end
+
+  defp package_source({:http, url}), do: package_source({:http, url, []})
+
+  defp package_source({:http, url, opts}) do
+    headers = Map.new(opts[:headers] || %{}, fn {name, value} -> {to_string(name), value} end)
+    {:http, %{url: url, headers: headers}}
+  end
+
+  defp package_source(source), do: source
+end
@@ -0,0 +1,76 @@
+defmodule Typster.Font do
+  @moduledoc """
+  A font available to Typst documents, as returned by `Typster.list_fonts/1`.
+
+  ## Fields
+
+  - `:family` - The font family name, as used in `#set text(font: ...)`
+  - `:style` - `:normal`, `:italic` or `:oblique`
+  - `:weight` - Weight from 100 (thin) to 900 (black); 400 is regular
+  - `:stretch` - Width relative to the normal width (e.g. `0.75` for condensed)
+  - `:flags` - List of `:monospace`, `:serif`, `:math` and `:variable`
+  - `:coverage` - Map with the number of `:code_points` the font covers and
+    the inclusive `:ranges` of code points as `{first, last}` tuples
+  - `:source` - Where the font comes from: `:system` (installed on the
+    system), `:font_path` (found in one of the `:font_paths`), `:embedded`
+    (bundled with Typster) or `:memory` (passed with the `:fonts` option)
+  - `:path` - File the font was loaded from, or `nil` for embedded and
+    in-memory fonts
+  - `:index` - Index of the font within a collection file (TTC), or `nil`
+
+  ## Examples
+
+      {:ok, fonts} = Typster.list_fonts()
+      fonts |> Enum.map(& &1.family) |> Enum.uniq()
+      #=> ["DejaVu Sans Mono", "Libertinus Serif", "New Computer Modern", ...]
+  """
+
+  @type style :: :normal | :italic | :oblique
+  @type flag :: :monospace | :serif | :math | :variable
+  @type source :: :system | :font_path | :embedded | :memory
+
+  @type coverage :: %{
+          code_points: non_neg_integer(),
+          ranges: [{non_neg_integer(), non_neg_integer()}]
+        }
+
+  @type t :: %__MODULE__{
+          family: String.t(),
+          style: style(),
+          weight: pos_integer(),
+          stretch: float(),
+          flags: [flag()],
+          coverage: coverage(),
+          source: source(),
+          path: String.t() | nil,
+          index: non_neg_integer() | nil
+        }
+
+  defstruct family: "",
+            style: :normal,
+            weight: 400,
+            stretch: 1.0,
+            flags: [],
+            coverage: %{code_points: 0, ranges: []},
+            source: :system,
+            path: nil,
+            index: nil
+
+  @doc """
+  Check whether the font covers a character or code point.
+
+  ## Examples
+
+      iex> font = %Typster.Font{coverage: %{code_points: 95, ranges: [{0x20, 0x7E}]}}
+      iex> Typster.Font.covers?(font, ?A)
+      true
+      iex> Typster.Font.covers?(font, "é")
+      false
+  """
+  @spec covers?(t(), String.t() | non_neg_integer()) :: boolean()
+  def covers?(%__MODULE__{} = font, <<code_point::utf8>>), do: covers?(font, code_point)
+
+  def covers?(%__MODULE__{coverage: %{ranges: ranges}}, code_point) when is_integer(code_point) do
+    Enum.any?(ranges, fn {first, last} -> code_point >= first and code_point <= last end)
+  end
+end
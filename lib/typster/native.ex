@@ -1,10 +1,36 @@
-defmodule Typster.Native.TypsterOptions do
+defmodule Typster.Native.EngineOptions do
   @moduledoc false
 
-  defstruct metadata: %{},
+  defstruct fonts: [],
+            font_paths: [],
+            ignore_system_fonts: false,
             package_paths: [],
-            pixel_per_pt: 2.0,
+            package_cache_dir: nil,
+            package_registry: nil,
+            package_namespaces: %{},
+            package_hashes: %{},
             root_path: ".",
+            sandbox: :disabled,
+            allowed_extensions: nil,
+            max_file_size: nil,
+            max_total_size: nil,
+            offline: false
+end
+
+defmodule Typster.Native.TypsterOptions do
+  @moduledoc false
+
+  defstruct creation_timestamp: nil,
+            document_id: nil,
+            engine: nil,
+            engine_options: %Typster.Native.EngineOptions{},
+            files: %{},
+            metadata: %{},
+            pages: nil,
+            pdf_standards: [],
+            pixel_per_pt: 2.0,
+            sys_inputs: false,
+            today: nil,
             variables: %{}
 end
 
@@ -51,6 +77,9 @@ defmodule Typster.Native do
   # If the NIF is not loaded, these fallback implementations will be called
 
   def test_nif, do: :erlang.nif_error(:nif_not_loaded)
+  def new_engine(_opts), do: :erlang.nif_error(:nif_not_loaded)
+  def refresh_fonts, do: :erlang.nif_error(:nif_not_loaded)
+  def list_fonts(_engine, _opts), do: :erlang.nif_error(:nif_not_loaded)
   def compile_to_pdf(_source, _opts), do: :erlang.nif_error(:nif_not_loaded)
   def compile_to_svg(_source, _opts), do: :erlang.nif_error(:nif_not_loaded)
   def compile_to_png(_source, _opts), do: :erlang.nif_error(:nif_not_loaded)
@@ -25,8 +25,9 @@ defmodule Typster.MixProject do
       extras: ["README.md"],
       source_ref: "v#{@version}",
       groups_for_modules: [
-        "Core API": [Typster],
-        Exceptions: [Typster.CompileError],
+        "Core API": [Typster, Typster.Engine],
+        Exceptions: [Typster.CompileError, Typster.AccessError, Typster.Diagnostic],
+        Fonts: [Typster.Font],
         "Native Interface": [Typster.Native]
       ]
     ]
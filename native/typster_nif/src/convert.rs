@@ -1,6 +1,9 @@
+use chrono::{DateTime, FixedOffset, NaiveDate};
 use rustler::{Env, Term};
 use std::collections::HashMap;
+use std::num::NonZeroUsize;
 use typst::foundations::{Array, Datetime, Dict, Str, Value};
+use typst::layout::PageRanges;
 
 use crate::TypstError;
 
@@ -263,10 +266,114 @@ fn convert_naive_datetime_to_datetime(map: &HashMap<String, Term>) -> Result<Val
     convert_datetime_to_datetime(map)
 }
 
+/// A date or a point in time passed in an option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateValue {
+    /// An Elixir Date
+    Date(NaiveDate),
+    /// An Elixir DateTime, or a NaiveDateTime taken as UTC
+    DateTime(DateTime<FixedOffset>),
+}
+
+/// Convert an Elixir Date, DateTime or NaiveDateTime passed as `option`
+///
+/// The struct is expected as a map with string keys, the way the Elixir side
+/// passes structs in variables. DateTimes keep their UTC offset, while
+/// NaiveDateTimes are taken as UTC.
+pub fn term_to_datetime(term: Term, option: &str) -> Result<DateValue, TypstError> {
+    let expected = || {
+        TypstError::InvalidInput(format!(
+            "Expected a Date, DateTime or NaiveDateTime for {}, got {}",
+            option,
+            get_term_type_name(term)
+        ))
+    };
+
+    let map: HashMap<String, Term> = term.decode().map_err(|_| expected())?;
+    let struct_name = map
+        .get("__struct__")
+        .and_then(|t| t.decode::<String>().ok())
+        .ok_or_else(expected)?;
+
+    if !matches!(
+        struct_name.as_str(),
+        "Elixir.Date" | "Elixir.DateTime" | "Elixir.NaiveDateTime"
+    ) {
+        return Err(TypstError::InvalidInput(format!(
+            "Expected a Date, DateTime or NaiveDateTime for {}, got {}",
+            option,
+            struct_name.trim_start_matches("Elixir.")
+        )));
+    }
+
+    let field = |name: &str| {
+        map.get(name)
+            .and_then(|t| t.decode::<i64>().ok())
+            .ok_or_else(|| {
+                TypstError::InvalidInput(format!("{} is missing the {} field", option, name))
+            })
+    };
+    let invalid = || TypstError::InvalidInput(format!("Invalid date values for {}", option));
+
+    let date = NaiveDate::from_ymd_opt(
+        field("year")? as i32,
+        field("month")? as u32,
+        field("day")? as u32,
+    )
+    .ok_or_else(invalid)?;
+
+    let offset = match struct_name.as_str() {
+        "Elixir.Date" => return Ok(DateValue::Date(date)),
+        "Elixir.DateTime" => field("utc_offset")? + field("std_offset")?,
+        _ => 0,
+    };
+
+    let offset = FixedOffset::east_opt(offset as i32).ok_or_else(invalid)?;
+    let now = date
+        .and_hms_opt(
+            field("hour")? as u32,
+            field("minute")? as u32,
+            field("second")? as u32,
+        )
+        .and_then(|time| time.and_local_timezone(offset).single())
+        .ok_or_else(invalid)?;
+
+    Ok(DateValue::DateTime(now))
+}
+
+/// Convert the `:pages` option to page ranges
+///
+/// Each term is a 1-based page number or a `{first, last}` tuple, which the
+/// Elixir side produces from `first..last` ranges.
+pub fn terms_to_page_ranges(terms: &[Term]) -> Result<PageRanges, TypstError> {
+    terms
+        .iter()
+        .map(|term| {
+            let (first, last) = term
+                .decode::<usize>()
+                .map(|page| (page, page))
+                .or_else(|_| term.decode::<(usize, usize)>())
+                .map_err(|_| {
+                    TypstError::InvalidInput(format!(
+                        "Invalid pages entry of type {}: expected a page number or a range of page numbers",
+                        get_term_type_name(*term)
+                    ))
+                })?;
+
+            match (NonZeroUsize::new(first), NonZeroUsize::new(last)) {
+                (Some(first), Some(last)) if first <= last => Ok(Some(first)..=Some(last)),
+                _ => Err(TypstError::InvalidInput(format!(
+                    "Invalid page range {}..{}: pages are numbered from 1 and ranges must be ascending",
+                    first, last
+                ))),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(PageRanges::new)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::*;
-
     // Note: These tests would require a Rustler environment to run
     // They are here as documentation of expected behavior
 }
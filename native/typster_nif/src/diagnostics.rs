@@ -0,0 +1,274 @@
+use rustler::{NifMap, NifStruct, NifUnitEnum};
+use typst::diag::{self, SourceDiagnostic, Tracepoint};
+use typst::syntax::{DiagSpan, FileId, Lines, Spanned, VirtualRoot};
+use typst::{World, WorldExt};
+
+use crate::world::TypstWorld;
+
+/// Severity of a diagnostic, encoded as `:error` or `:warning`
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A compiler diagnostic with its location resolved to lines and columns
+#[derive(NifStruct, Debug, Clone)]
+#[module = "Typster.Diagnostic"]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+    pub snippet: Option<String>,
+    pub hints: Vec<String>,
+    pub trace: Vec<TracePoint>,
+}
+
+impl Diagnostic {
+    /// An error that isn't tied to a location in the template
+    pub fn error(message: impl Into<String>, hints: Vec<String>) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            path: None,
+            line: None,
+            column: None,
+            end_line: None,
+            end_column: None,
+            snippet: None,
+            hints,
+            trace: Vec::new(),
+        }
+    }
+}
+
+/// One step of the call/import trace leading to a diagnostic
+#[derive(NifMap, Debug, Clone)]
+pub struct TracePoint {
+    pub message: String,
+    pub path: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub end_line: Option<usize>,
+    pub end_column: Option<usize>,
+}
+
+/// A span resolved against the world's files
+///
+/// Lines and columns are 1-based; columns count characters, not bytes.
+#[derive(Debug, Default)]
+struct Location {
+    path: Option<String>,
+    line: Option<usize>,
+    column: Option<usize>,
+    end_line: Option<usize>,
+    end_column: Option<usize>,
+    snippet: Option<String>,
+}
+
+/// Convert Typst diagnostics into their Elixir representation
+pub fn convert(world: &TypstWorld, diagnostics: &[SourceDiagnostic]) -> Vec<Diagnostic> {
+    diagnostics.iter().map(|diagnostic| convert_one(world, diagnostic)).collect()
+}
+
+fn convert_one(world: &TypstWorld, diagnostic: &SourceDiagnostic) -> Diagnostic {
+    let location = locate(world, diagnostic.span);
+
+    let severity = match diagnostic.severity {
+        diag::Severity::Error => Severity::Error,
+        diag::Severity::Warning => Severity::Warning,
+    };
+
+    let hints = diagnostic
+        .hints
+        .iter()
+        .map(|hint| hint.v.to_string())
+        .collect();
+
+    let trace = diagnostic
+        .trace
+        .iter()
+        .map(|point| convert_tracepoint(world, point))
+        .collect();
+
+    Diagnostic {
+        severity,
+        message: diagnostic.message.to_string(),
+        path: location.path,
+        line: location.line,
+        column: location.column,
+        end_line: location.end_line,
+        end_column: location.end_column,
+        snippet: location.snippet,
+        hints,
+        trace,
+    }
+}
+
+fn convert_tracepoint(world: &TypstWorld, point: &Spanned<Tracepoint>) -> TracePoint {
+    let location = locate(world, point.span.into());
+
+    TracePoint {
+        message: point.v.to_string(),
+        path: location.path,
+        line: location.line,
+        column: location.column,
+        end_line: location.end_line,
+        end_column: location.end_column,
+    }
+}
+
+/// Resolve a diagnostic span to a file path, line/column range and snippet
+///
+/// Positions in the main file are reported relative to the user's template,
+/// skipping the generated variable import. Spans inside the import itself
+/// have no line information since they don't correspond to anything the user
+/// wrote.
+fn locate(world: &TypstWorld, span: DiagSpan) -> Location {
+    let Some(id) = span.id() else {
+        return Location::default();
+    };
+
+    let mut location = Location {
+        path: Some(display_path(id)),
+        ..Default::default()
+    };
+
+    let (Some(range), Some(lines)) = (world.range(span), file_lines(world, id)) else {
+        return location;
+    };
+
+    let (prefix_len, prefix_lines) = if id == world.main() {
+        let prefix_len = world.prefix_len();
+        (prefix_len, lines.byte_to_line(prefix_len).unwrap_or(0))
+    } else {
+        (0, 0)
+    };
+
+    if range.start < prefix_len {
+        return location;
+    }
+
+    if let Some((line, column)) = lines.byte_to_line_column(range.start) {
+        location.line = Some(line - prefix_lines + 1);
+        location.column = Some(column + 1);
+    }
+
+    if let Some((line, column)) = lines.byte_to_line_column(range.end) {
+        location.end_line = Some(line - prefix_lines + 1);
+        location.end_column = Some(column + 1);
+    }
+
+    location.snippet = lines.text().get(range).map(str::to_string);
+    location
+}
+
+/// Get the text of a file for line lookups, whether or not it is Typst source
+fn file_lines(world: &TypstWorld, id: FileId) -> Option<Lines<String>> {
+    if let Ok(source) = world.source(id) {
+        return Some(source.lines().clone());
+    }
+
+    let bytes = world.file(id).ok()?;
+    let text = std::str::from_utf8(&bytes).ok()?;
+    Some(Lines::new(text.to_string()))
+}
+
+/// Human-readable path of a file, prefixed with its package spec if any
+fn display_path(id: FileId) -> String {
+    match id.root() {
+        VirtualRoot::Project => id.vpath().get_without_slash().to_string(),
+        VirtualRoot::Package(package) => format!("{}{}", package, id.vpath().get_with_slash()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, EngineConfig};
+    use std::sync::Arc;
+    use typst::foundations::{Dict, Value};
+    use typst_layout::PagedDocument;
+
+    fn engine() -> Arc<Engine> {
+        Arc::new(Engine::new(EngineConfig::default()).unwrap())
+    }
+
+    fn check(source: &str) -> Vec<Diagnostic> {
+        check_with(source, Dict::new())
+    }
+
+    fn check_with(source: &str, variables: Dict) -> Vec<Diagnostic> {
+        let world = TypstWorld::new(engine(), source.to_string(), variables, Dict::new()).unwrap();
+
+        match typst::compile::<PagedDocument>(&world).output {
+            Ok(_) => Vec::new(),
+            Err(errors) => convert(&world, &errors),
+        }
+    }
+
+    #[test]
+    fn resolves_error_location() {
+        let diagnostics = check("= Title\nHello #unknown_var");
+
+        assert_eq!(diagnostics.len(), 1);
+        let diagnostic = &diagnostics[0];
+        assert_eq!(diagnostic.severity, Severity::Error);
+        assert_eq!(diagnostic.message, "unknown variable: unknown_var");
+        assert_eq!(diagnostic.path.as_deref(), Some("main.typ"));
+        assert_eq!(diagnostic.line, Some(2));
+        assert_eq!(diagnostic.column, Some(8));
+        assert_eq!(diagnostic.end_line, Some(2));
+        assert_eq!(diagnostic.end_column, Some(19));
+        assert_eq!(diagnostic.snippet.as_deref(), Some("unknown_var"));
+    }
+
+    #[test]
+    fn includes_trace_for_errors_inside_functions() {
+        let diagnostics = check("#let f(x) = x + \"a\"\n#f(1)");
+
+        assert_eq!(diagnostics.len(), 1);
+        let trace = &diagnostics[0].trace;
+        assert_eq!(trace.len(), 1);
+        assert_eq!(trace[0].message, "while calling `f`");
+        assert_eq!(trace[0].line, Some(2));
+    }
+
+    #[test]
+    fn converts_warnings() {
+        let world = TypstWorld::new(
+            engine(),
+            "#set text(font: \"No Such Font\")\nHello".to_string(),
+            Dict::new(),
+            Dict::new(),
+        )
+        .unwrap();
+
+        let warned = typst::compile::<PagedDocument>(&world);
+        assert!(warned.output.is_ok());
+
+        let warnings = convert(&world, &warned.warnings);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].severity, Severity::Warning);
+        assert_eq!(warnings[0].message, "unknown font family: no such font");
+        assert_eq!(warnings[0].line, Some(1));
+    }
+
+    #[test]
+    fn reports_lines_relative_to_the_template() {
+        let mut variables = Dict::new();
+        variables.insert("a".into(), Value::Int(1));
+        variables.insert("b".into(), Value::Str("two".into()));
+
+        let diagnostics = check_with("= Title\nHello #unknown_var", variables);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].line, Some(2));
+        assert_eq!(diagnostics[0].column, Some(8));
+        assert_eq!(diagnostics[0].end_line, Some(2));
+    }
+}
@@ -0,0 +1,568 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use typst::diag::{FileError, FileResult, PackageError};
+use typst::foundations::Bytes;
+use typst::syntax::package::PackageSpec;
+use typst::syntax::{FileId, Source, VirtualRoot};
+use typst::{Library, LibraryExt};
+use typst_kit::fonts::FontStore;
+
+use crate::fonts::{self, FontOptions};
+use crate::packages::{self, PackageSource, Registry};
+use crate::sandbox::{FileLimits, Sandbox, SandboxMode};
+use crate::TypstError;
+
+/// Number of renders a cached file or source is kept for without being used
+const MAX_CACHE_AGE: usize = 10;
+
+/// State shared by every render that uses the same engine
+///
+/// Long-running applications should create one engine and pass it to every
+/// render, so the standard library and the files read from disk are reused.
+pub struct Engine {
+    /// The root directory for resolving files
+    root: PathBuf,
+    /// Additional package directories to search
+    package_paths: Vec<PathBuf>,
+    /// Directory for caching downloaded packages
+    package_cache_dir: PathBuf,
+    /// Where missing packages are downloaded from
+    registry: Registry,
+    /// Sources of the namespaces not served by the registry
+    namespaces: HashMap<String, PackageSource>,
+    /// SHA-256 digests downloaded package archives must match
+    package_hashes: HashMap<PackageSpec, String>,
+    /// The standard library without any variables or inputs
+    library: Library,
+    /// Font store providing the font book and lazily-loaded fonts
+    fonts: Arc<FontStore>,
+    /// Contents of files read from disk
+    files: FileCache,
+    /// Parsed sources of the files imported by documents
+    sources: SourceCache,
+    /// Restrictions on the files documents may load
+    sandbox: Sandbox,
+    /// Whether packages missing from disk fail instead of being downloaded
+    offline: bool,
+    /// Mutex to ensure thread-safe package downloads
+    download_lock: Mutex<()>,
+}
+
+/// Settings an engine is created with
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    /// The root directory for resolving files
+    pub root: PathBuf,
+    /// Additional package directories to search
+    pub package_paths: Vec<PathBuf>,
+    /// Directory for caching downloaded packages, instead of the directory
+    /// shared with the Typst CLI
+    pub package_cache_dir: Option<PathBuf>,
+    /// URL of the registry packages are downloaded from, instead of the
+    /// official registry
+    pub registry: Option<String>,
+    /// Where the packages of private namespaces come from, by namespace
+    pub namespaces: HashMap<String, PackageSource>,
+    /// SHA-256 digests package archives are pinned to, by package spec
+    pub package_hashes: HashMap<String, String>,
+    /// Which fonts are available to documents
+    pub fonts: FontOptions,
+    /// Restrictions on reading project files from disk
+    pub sandbox: SandboxMode,
+    /// Limits on the files documents may load
+    pub limits: FileLimits,
+    /// Never download packages, only use the ones already on disk
+    pub offline: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("."),
+            package_paths: Vec::new(),
+            package_cache_dir: None,
+            registry: None,
+            namespaces: HashMap::new(),
+            package_hashes: HashMap::new(),
+            fonts: FontOptions::default(),
+            sandbox: SandboxMode::default(),
+            limits: FileLimits::default(),
+            offline: false,
+        }
+    }
+}
+
+/// Handle to an engine, passed to and from Elixir
+pub struct EngineResource(pub Arc<Engine>);
+
+#[rustler::resource_impl]
+impl rustler::Resource for EngineResource {}
+
+// The engine is only mutated behind mutexes, so observing it after a panic
+// can't expose a broken invariant. Rustler requires this to return the handle.
+impl std::panic::RefUnwindSafe for EngineResource {}
+
+impl Engine {
+    /// Create an engine with the given settings
+    pub fn new(config: EngineConfig) -> Result<Self, TypstError> {
+        let sandbox = Sandbox::new(config.sandbox, &config.root, config.limits)?;
+        let package_cache_dir = match config.package_cache_dir {
+            Some(dir) => dir,
+            None => packages::get_cache_dir()?,
+        };
+        let registry = match &config.registry {
+            Some(url) => Registry::from_url(url)?,
+            None => Registry::default(),
+        };
+
+        Ok(Self {
+            root: config.root,
+            package_paths: config.package_paths,
+            package_cache_dir,
+            registry,
+            namespaces: config.namespaces,
+            package_hashes: packages::parse_hashes(&config.package_hashes)?,
+            library: Library::default(),
+            fonts: fonts::load(&config.fonts)?,
+            files: FileCache::default(),
+            sources: SourceCache::default(),
+            sandbox,
+            // Engines that never read project files from disk don't download
+            // packages either
+            offline: config.offline || config.sandbox == SandboxMode::Memory,
+            download_lock: Mutex::new(()),
+        })
+    }
+
+    /// Drop the cached files and sources that weren't used by the last renders
+    /// with this engine
+    ///
+    /// Called after every render, so a long-lived engine only keeps what the
+    /// documents it currently renders need instead of everything it has ever
+    /// loaded.
+    pub fn evict(&self) {
+        self.files.evict(MAX_CACHE_AGE);
+        self.sources.evict(MAX_CACHE_AGE);
+    }
+
+    /// The standard library without any variables or inputs
+    pub fn library(&self) -> &Library {
+        &self.library
+    }
+
+    /// The fonts available to documents rendered with this engine
+    pub fn fonts(&self) -> &Arc<FontStore> {
+        &self.fonts
+    }
+
+    /// Restrictions on the files documents may load
+    pub fn sandbox(&self) -> &Sandbox {
+        &self.sandbox
+    }
+
+    /// Read a file from disk, reusing the cached contents if it hasn't changed
+    pub fn read(&self, path: &Path) -> FileResult<Bytes> {
+        self.files
+            .read(path)
+            .map_err(|e| FileError::from_io(e, path))
+    }
+
+    /// Parse the contents of the file `id`, reusing the previously parsed
+    /// source if the text hasn't changed
+    pub fn source(&self, id: FileId, bytes: &Bytes) -> FileResult<Source> {
+        let text = std::str::from_utf8(bytes).map_err(|_| FileError::InvalidUtf8)?;
+        Ok(self.sources.get(id, text))
+    }
+
+    /// Resolve a FileId to an actual file system path
+    pub fn resolve_path(&self, id: FileId) -> FileResult<PathBuf> {
+        // Check if this is a package file
+        if let VirtualRoot::Package(package) = id.root() {
+            // Try to find the package in configured package_paths first
+            for package_root in &self.package_paths {
+                // Package format: @namespace/name/version
+                let package_dir = package_root
+                    .join(package.namespace.as_str())
+                    .join(package.name.as_str())
+                    .join(package.version.to_string());
+
+                if let Ok(resolved) = id.vpath().realize(&package_dir) {
+                    if resolved.exists() {
+                        return Ok(resolved);
+                    }
+                }
+            }
+
+            let source = self.namespaces.get(package.namespace.as_str());
+
+            // Packages of namespaces served from a directory are used in place
+            if let Some(package_dir) = source.and_then(|source| source.local_dir(package)) {
+                return match id.vpath().realize(&package_dir) {
+                    Ok(resolved) if package_dir.is_dir() => Ok(resolved),
+                    _ => Err(FileError::Package(PackageError::NotFound(package.clone()))),
+                };
+            }
+
+            // Not found in package_paths, try the cache directory
+            let cache_package_dir = self
+                .package_cache_dir
+                .join(package.namespace.as_str())
+                .join(package.name.as_str())
+                .join(package.version.to_string());
+
+            let realize = |package_dir: &Path| {
+                id.vpath()
+                    .realize(package_dir)
+                    .map_err(|_| FileError::NotFound(id.vpath().get_without_slash().into()))
+            };
+
+            // Packages whose extraction didn't finish, and pinned packages
+            // that weren't verified against their digest, are downloaded again
+            let hash = self.package_hashes.get(package).map(String::as_str);
+            if packages::is_usable(&cache_package_dir, hash) {
+                return realize(&cache_package_dir);
+            }
+
+            // Not in cache either, so it has to be downloaded
+            if self.offline {
+                let message = if packages::is_installed(&cache_package_dir) {
+                    format!(
+                        "package {} in the cache isn't verified against its pinned digest, \
+                         and can't be downloaded again offline",
+                        package
+                    )
+                } else {
+                    format!("package {} not available offline", package)
+                };
+                return Err(FileError::Package(PackageError::Other(Some(
+                    message.into(),
+                ))));
+            }
+
+            // Use a lock to prevent concurrent downloads of the same package
+            let _lock = self.download_lock.lock().unwrap();
+
+            // Check again after acquiring lock (another thread might have downloaded it)
+            if packages::is_usable(&cache_package_dir, hash) {
+                return realize(&cache_package_dir);
+            }
+
+            // Download the package from its namespace's source or the registry
+            let source = source
+                .cloned()
+                .unwrap_or_else(|| self.registry.source(package.namespace.as_str()));
+            let downloaded_dir =
+                packages::download_package(package, &source, &self.package_cache_dir, hash)
+                    .map_err(|e| FileError::Other(Some(e.to_string().into())))?;
+
+            // Now try to resolve the path again
+            realize(&downloaded_dir)
+        } else {
+            // Not a package file, resolve relative to root
+            id.vpath()
+                .realize(&self.root)
+                .map_err(|_| FileError::NotFound(id.vpath().get_without_slash().into()))
+        }
+    }
+}
+
+/// Contents of files read from disk, keyed by path
+///
+/// Entries are revalidated against the file's modification time and size on
+/// every read, so templates and assets edited on disk are picked up by the
+/// next render. Entries unused for a number of renders are evicted.
+#[derive(Default)]
+struct FileCache {
+    entries: Mutex<HashMap<PathBuf, CachedFile>>,
+}
+
+struct CachedFile {
+    modified: SystemTime,
+    len: u64,
+    bytes: Bytes,
+    /// Evictions since the file was last read
+    age: usize,
+}
+
+impl FileCache {
+    fn read(&self, path: &Path) -> io::Result<Bytes> {
+        let metadata = fs::metadata(path)?;
+        let Ok(modified) = metadata.modified() else {
+            // Without modification times we can't tell stale entries apart
+            return Ok(Bytes::new(fs::read(path)?));
+        };
+
+        if let Some(cached) = self.entries.lock().unwrap().get_mut(path) {
+            if cached.modified == modified && cached.len == metadata.len() {
+                cached.age = 0;
+                return Ok(cached.bytes.clone());
+            }
+        }
+
+        let bytes = Bytes::new(fs::read(path)?);
+        self.entries.lock().unwrap().insert(
+            path.to_path_buf(),
+            CachedFile {
+                modified,
+                len: metadata.len(),
+                bytes: bytes.clone(),
+                age: 0,
+            },
+        );
+
+        Ok(bytes)
+    }
+
+    /// Drop the files that weren't read in the last `max_age` evictions
+    fn evict(&self, max_age: usize) {
+        self.entries.lock().unwrap().retain(|_, cached| {
+            cached.age += 1;
+            cached.age <= max_age
+        });
+    }
+}
+
+/// Parsed sources, keyed by file
+///
+/// A source whose text changed since it was last parsed is reparsed
+/// incrementally, reusing the unchanged parts of its syntax tree. Entries
+/// unused for a number of renders are evicted.
+#[derive(Default)]
+struct SourceCache {
+    entries: Mutex<HashMap<FileId, CachedSource>>,
+}
+
+struct CachedSource {
+    source: Source,
+    /// Evictions since the source was last used
+    age: usize,
+}
+
+impl SourceCache {
+    fn get(&self, id: FileId, text: &str) -> Source {
+        let cached = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(&id)
+            .map(|cached| cached.source.clone());
+        let source = match cached {
+            Some(source) if source.text() == text => source,
+            Some(mut source) => {
+                source.replace(text);
+                source
+            }
+            None => Source::new(id, text.to_string()),
+        };
+
+        self.entries.lock().unwrap().insert(
+            id,
+            CachedSource {
+                source: source.clone(),
+                age: 0,
+            },
+        );
+        source
+    }
+
+    /// Drop the sources that weren't used in the last `max_age` evictions
+    fn evict(&self, max_age: usize) {
+        self.entries.lock().unwrap().retain(|_, cached| {
+            cached.age += 1;
+            cached.age <= max_age
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use typst::syntax::{RootedPath, VirtualPath};
+
+    #[test]
+    fn file_cache_picks_up_changes_on_disk() {
+        let dir = std::env::temp_dir().join(format!("typster-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.txt");
+        let cache = FileCache::default();
+
+        fs::write(&path, "one").unwrap();
+        assert_eq!(cache.read(&path).unwrap().as_slice(), b"one");
+
+        fs::write(&path, "two!").unwrap();
+        let file = fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(cache.read(&path).unwrap().as_slice(), b"two!");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn caches_evict_entries_unused_for_too_long() {
+        let dir = std::env::temp_dir().join(format!("typster-evict-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (used, unused) = (dir.join("used.typ"), dir.join("unused.typ"));
+        fs::write(&used, "#let a = 1").unwrap();
+        fs::write(&unused, "#let b = 2").unwrap();
+
+        let files = FileCache::default();
+        let sources = SourceCache::default();
+        let id = |name| {
+            FileId::new(RootedPath::new(
+                VirtualRoot::Project,
+                VirtualPath::new(name).unwrap(),
+            ))
+        };
+
+        files.read(&unused).unwrap();
+        sources.get(id("unused.typ"), "#let b = 2");
+        for _ in 0..3 {
+            files.read(&used).unwrap();
+            sources.get(id("used.typ"), "#let a = 1");
+            files.evict(2);
+            sources.evict(2);
+        }
+
+        let files = files.entries.lock().unwrap();
+        assert!(files.contains_key(&used));
+        assert!(!files.contains_key(&unused));
+        let sources = sources.entries.lock().unwrap();
+        assert!(sources.contains_key(&id("used.typ")));
+        assert!(!sources.contains_key(&id("unused.typ")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn source_cache_reparses_changed_text() {
+        let path = VirtualPath::new("lib.typ").unwrap();
+        let id = FileId::new(RootedPath::new(VirtualRoot::Project, path));
+        let cache = SourceCache::default();
+
+        let source = cache.get(id, "#let a = 1");
+        assert_eq!(source.text(), "#let a = 1");
+        assert_eq!(cache.get(id, "#let a = 1").root(), source.root());
+
+        let source = cache.get(id, "#let a = 12");
+        assert_eq!(source.text(), "#let a = 12");
+        assert_eq!(source.root().len(), "#let a = 12".len());
+        assert!(source.root().errors_and_warnings().0.is_empty());
+    }
+
+    #[test]
+    fn offline_engines_do_not_download_packages() {
+        let engine = Engine::new(EngineConfig {
+            offline: true,
+            ..EngineConfig::default()
+        })
+        .unwrap();
+
+        let spec = "@preview/typster-missing:0.1.0".parse().unwrap();
+        let path = VirtualPath::new("lib.typ").unwrap();
+        let id = FileId::new(RootedPath::new(VirtualRoot::Package(spec), path));
+
+        let error = engine.resolve_path(id).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("package @preview/typster-missing:0.1.0 not available offline"));
+    }
+
+    #[test]
+    fn resolves_namespaces_served_from_a_directory() {
+        let dir = std::env::temp_dir().join(format!("typster-namespace-{}", std::process::id()));
+        fs::create_dir_all(dir.join("hello/1.0.0")).unwrap();
+        fs::write(dir.join("hello/1.0.0/lib.typ"), "#let hello = [Hi]").unwrap();
+
+        let engine = Engine::new(EngineConfig {
+            namespaces: HashMap::from([(
+                "acme".to_string(),
+                PackageSource::Directory(dir.to_string_lossy().into_owned()),
+            )]),
+            ..EngineConfig::default()
+        })
+        .unwrap();
+
+        let package_file = |spec: &str| {
+            let path = VirtualPath::new("lib.typ").unwrap();
+            FileId::new(RootedPath::new(
+                VirtualRoot::Package(spec.parse().unwrap()),
+                path,
+            ))
+        };
+
+        assert_eq!(
+            engine
+                .resolve_path(package_file("@acme/hello:1.0.0"))
+                .unwrap(),
+            dir.join("hello/1.0.0/lib.typ")
+        );
+
+        // Missing packages aren't looked up in the public registry
+        let error = engine
+            .resolve_path(package_file("@acme/hello:2.0.0"))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "package not found (searched for @acme/hello:2.0.0)"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn offline_engines_ignore_incomplete_packages() {
+        let cache = std::env::temp_dir().join(format!("typster-incomplete-{}", std::process::id()));
+        let package_dir = cache.join("preview/partial/0.1.0");
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(package_dir.join("lib.typ"), "#let partial = [").unwrap();
+
+        let engine = Engine::new(EngineConfig {
+            package_cache_dir: Some(cache.clone()),
+            offline: true,
+            ..EngineConfig::default()
+        })
+        .unwrap();
+
+        let spec = "@preview/partial:0.1.0".parse().unwrap();
+        let path = VirtualPath::new("lib.typ").unwrap();
+        let id = FileId::new(RootedPath::new(VirtualRoot::Package(spec), path));
+
+        let error = engine.resolve_path(id).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("package @preview/partial:0.1.0 not available offline"));
+
+        fs::write(package_dir.join("typst.toml"), "[package]").unwrap();
+        assert_eq!(
+            engine.resolve_path(id).unwrap(),
+            package_dir.join("lib.typ")
+        );
+
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn memory_sandbox_engines_do_not_download_packages() {
+        let engine = Engine::new(EngineConfig {
+            sandbox: SandboxMode::Memory,
+            ..EngineConfig::default()
+        })
+        .unwrap();
+
+        let spec = "@preview/typster-missing:0.1.0".parse().unwrap();
+        let path = VirtualPath::new("lib.typ").unwrap();
+        let id = FileId::new(RootedPath::new(VirtualRoot::Package(spec), path));
+
+        let error = engine.resolve_path(id).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("package @preview/typster-missing:0.1.0 not available offline"));
+    }
+}
@@ -0,0 +1,400 @@
+use std::any::Any;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rustler::{NifMap, NifStruct, NifUnitEnum};
+use typst::foundations::Bytes;
+use typst::text::{Font, FontFlags, FontInfo, FontStyle};
+use typst_kit::fonts::{self, FontPath, FontSource, FontStore};
+
+use crate::TypstError;
+
+/// Which fonts are made available to documents
+#[derive(Debug, Clone, Default)]
+pub struct FontOptions {
+    /// Font files (TTF, OTF or collections) passed in memory
+    pub font_data: Vec<Bytes>,
+    /// Directories searched recursively for additional fonts
+    pub font_paths: Vec<PathBuf>,
+    /// Leave out the fonts installed on the system
+    pub ignore_system_fonts: bool,
+}
+
+/// A font passed in memory rather than found on disk
+pub struct MemoryFont(Font);
+
+impl FontSource for MemoryFont {
+    fn load(&self) -> Option<Font> {
+        Some(self.0.clone())
+    }
+}
+
+/// A font found in one of the `font_paths` rather than in the system fonts
+pub struct DirectoryFont(FontPath);
+
+impl FontSource for DirectoryFont {
+    fn load(&self) -> Option<Font> {
+        self.0.load()
+    }
+}
+
+/// Where a font made available to documents comes from
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    System,
+    FontPath,
+    Embedded,
+    Memory,
+}
+
+/// Style of a font, encoded as `:normal`, `:italic` or `:oblique`
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// Characteristics of a font, encoded as atoms
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    Monospace,
+    Serif,
+    Math,
+    Variable,
+}
+
+/// Unicode coverage of a font as inclusive ranges of code points
+#[derive(NifMap, Debug, Clone)]
+pub struct Coverage {
+    pub code_points: usize,
+    pub ranges: Vec<(u32, u32)>,
+}
+
+/// A font in a font store, as listed to Elixir
+#[derive(NifStruct, Debug, Clone)]
+#[module = "Typster.Font"]
+pub struct FontDescription {
+    pub family: String,
+    pub style: Style,
+    pub weight: u16,
+    pub stretch: f64,
+    pub flags: Vec<Flag>,
+    pub coverage: Coverage,
+    pub source: Origin,
+    pub path: Option<String>,
+    pub index: Option<u32>,
+}
+
+/// Result of the process-wide system font scan
+struct SharedFonts {
+    /// Fonts found in the operating system's font directories
+    system: Vec<(FontPath, FontInfo)>,
+    /// Font store with the system and embedded fonts
+    store: Arc<FontStore>,
+}
+
+/// Process-wide system font scan, shared by every engine
+///
+/// Scanning the system fonts is by far the most expensive part of setting up a
+/// render, so it happens once, on first use. Fonts installed afterwards are
+/// only picked up after [`refresh`].
+static SHARED_FONTS: Mutex<Option<Arc<SharedFonts>>> = Mutex::new(None);
+
+/// Get the font store for the given options
+///
+/// With the default options this is the shared store of system and embedded
+/// fonts. Otherwise a new store is built with the in-memory fonts and the
+/// fonts from `font_paths` first, so they take precedence, followed by the
+/// system fonts (unless ignored) and the embedded fonts.
+pub fn load(options: &FontOptions) -> Result<Arc<FontStore>, TypstError> {
+    if options.font_data.is_empty() && options.font_paths.is_empty() && !options.ignore_system_fonts
+    {
+        return Ok(shared_fonts().store.clone());
+    }
+
+    let mut store = FontStore::new();
+    for (index, data) in options.font_data.iter().enumerate() {
+        let fonts: Vec<_> = Font::iter(data.clone()).collect();
+        if fonts.is_empty() {
+            return Err(TypstError::InvalidInput(format!(
+                "Font at index {} is not a valid TTF, OTF or TTC font",
+                index
+            )));
+        }
+        store.extend(fonts.into_iter().map(|font| {
+            let info = font.info().clone();
+            (MemoryFont(font), info)
+        }));
+    }
+
+    for path in &options.font_paths {
+        if !path.is_dir() {
+            return Err(TypstError::InvalidInput(format!(
+                "Font path '{}' is not a directory",
+                path.display()
+            )));
+        }
+        store.extend(fonts::scan(path).map(|(path, info)| (DirectoryFont(path), info)));
+    }
+
+    if !options.ignore_system_fonts {
+        let shared = shared_fonts();
+        store.extend(copy_entries(&shared.system));
+    }
+
+    store.extend(fonts::embedded());
+    Ok(Arc::new(store))
+}
+
+/// Rescan the system fonts used by engines created from now on
+///
+/// Engines created before the refresh keep using the fonts they were created
+/// with.
+pub fn refresh() {
+    let fonts = Arc::new(scan_system_fonts());
+    *SHARED_FONTS.lock().unwrap() = Some(fonts);
+}
+
+/// Get the shared system fonts, scanning them on first use
+fn shared_fonts() -> Arc<SharedFonts> {
+    // Hold the lock while scanning so concurrent first renders wait for a
+    // single scan instead of each walking the font directories
+    let mut shared = SHARED_FONTS.lock().unwrap();
+    shared
+        .get_or_insert_with(|| Arc::new(scan_system_fonts()))
+        .clone()
+}
+
+/// Scan the system fonts and build the default font store from them
+fn scan_system_fonts() -> SharedFonts {
+    let system: Vec<_> = fonts::system().collect();
+
+    let mut store = FontStore::new();
+    // System fonts first, so user-installed fonts take precedence, then the
+    // embedded fallback fonts guarantee a baseline set is always available.
+    store.extend(copy_entries(&system));
+    store.extend(fonts::embedded());
+
+    SharedFonts {
+        system,
+        store: Arc::new(store),
+    }
+}
+
+/// Describe the fonts in a store, in the order they are preferred
+pub fn describe(store: &FontStore) -> Vec<FontDescription> {
+    let book = store.book();
+    (0..)
+        .map_while(|index| Some((book.info(index)?, store.source(index)?)))
+        .map(|(info, source)| describe_one(info, source))
+        .collect()
+}
+
+fn describe_one(info: &FontInfo, source: &dyn FontSource) -> FontDescription {
+    let source = source as &dyn Any;
+    let (origin, location) = if let Some(font) = source.downcast_ref::<DirectoryFont>() {
+        (Origin::FontPath, Some(&font.0))
+    } else if let Some(path) = source.downcast_ref::<FontPath>() {
+        (Origin::System, Some(path))
+    } else if source.is::<MemoryFont>() {
+        (Origin::Memory, None)
+    } else {
+        (Origin::Embedded, None)
+    };
+
+    let style = match info.variant.style {
+        FontStyle::Normal => Style::Normal,
+        FontStyle::Italic => Style::Italic,
+        FontStyle::Oblique => Style::Oblique,
+    };
+
+    let flags = [
+        (FontFlags::MONOSPACE, Flag::Monospace),
+        (FontFlags::SERIF, Flag::Serif),
+        (FontFlags::MATH, Flag::Math),
+        (FontFlags::VARIABLE, Flag::Variable),
+    ]
+    .into_iter()
+    .filter(|(font_flag, _)| info.flags.contains(*font_flag))
+    .map(|(_, flag)| flag)
+    .collect();
+
+    FontDescription {
+        family: info.family.clone(),
+        style,
+        weight: info.variant.weight.to_number(),
+        stretch: info.variant.stretch.to_ratio().get(),
+        flags,
+        coverage: summarize_coverage(info),
+        source: origin,
+        path: location.map(|path| path.path.display().to_string()),
+        index: location.map(|path| path.index),
+    }
+}
+
+/// Collapse the code points covered by a font into contiguous ranges
+fn summarize_coverage(info: &FontInfo) -> Coverage {
+    let mut code_points = 0;
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for c in info.coverage.iter() {
+        code_points += 1;
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == c => *end = c,
+            _ => ranges.push((c, c)),
+        }
+    }
+
+    Coverage {
+        code_points,
+        ranges,
+    }
+}
+
+/// Copy scanned font entries so they can be added to another store
+fn copy_entries(
+    entries: &[(FontPath, FontInfo)],
+) -> impl Iterator<Item = (FontPath, FontInfo)> + '_ {
+    entries.iter().map(|(path, info)| {
+        let path = FontPath {
+            path: path.path.clone(),
+            index: path.index,
+        };
+        (path, info.clone())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn default_fonts() -> Arc<FontStore> {
+        load(&FontOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn shares_one_font_store_until_refreshed() {
+        let first = default_fonts();
+        assert!(Arc::ptr_eq(&first, &default_fonts()));
+
+        refresh();
+        let refreshed = default_fonts();
+        assert!(!Arc::ptr_eq(&first, &refreshed));
+        assert_eq!(
+            first.book().families().count(),
+            refreshed.book().families().count()
+        );
+    }
+
+    #[test]
+    fn ignores_system_fonts() {
+        let options = FontOptions {
+            ignore_system_fonts: true,
+            ..Default::default()
+        };
+        let store = load(&options).unwrap();
+
+        let embedded = fonts::embedded().count();
+        assert!((0..embedded).all(|index| store.font(index).is_some()));
+        assert!(store.font(embedded).is_none());
+    }
+
+    #[test]
+    fn loads_fonts_from_font_paths() {
+        let dir = std::env::temp_dir().join(format!("typster-fonts-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (font, info) = fonts::embedded().next().unwrap();
+        std::fs::write(dir.join("font.otf"), font.data().as_slice()).unwrap();
+
+        let options = FontOptions {
+            font_paths: vec![dir.clone()],
+            ignore_system_fonts: true,
+            ..Default::default()
+        };
+        let store = load(&options).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Fonts from the font paths come before the embedded fonts
+        let first = store.book().info(0).unwrap();
+        assert_eq!(first.family, info.family);
+        let fonts = describe(&store);
+        assert_eq!(fonts[0].source, Origin::FontPath);
+        assert_eq!(
+            fonts[0].path,
+            Some(dir.join("font.otf").display().to_string())
+        );
+        assert_eq!(fonts[0].index, Some(0));
+    }
+
+    #[test]
+    fn loads_fonts_from_memory() {
+        let (font, info) = fonts::embedded().next().unwrap();
+        let options = FontOptions {
+            font_data: vec![font.data().clone()],
+            ignore_system_fonts: true,
+            ..Default::default()
+        };
+        let store = load(&options).unwrap();
+
+        assert_eq!(store.book().info(0).unwrap().family, info.family);
+        assert!(store.font(0).is_some());
+        assert_eq!(describe(&store)[0].source, Origin::Memory);
+    }
+
+    #[test]
+    fn describes_embedded_fonts() {
+        let options = FontOptions {
+            ignore_system_fonts: true,
+            ..Default::default()
+        };
+        let fonts = describe(&load(&options).unwrap());
+
+        let font = fonts
+            .iter()
+            .find(|font| font.family == "Libertinus Serif" && font.style == Style::Normal)
+            .unwrap();
+        assert_eq!(font.source, Origin::Embedded);
+        assert_eq!(font.path, None);
+        assert_eq!(font.weight, 400);
+        assert_eq!(font.stretch, 1.0);
+        assert!(font.coverage.ranges.contains(&(0x20, 0x7E)));
+        assert_eq!(
+            font.coverage.code_points,
+            font.coverage
+                .ranges
+                .iter()
+                .map(|(start, end)| (end - start + 1) as usize)
+                .sum::<usize>()
+        );
+
+        let mono = fonts
+            .iter()
+            .find(|font| font.family == "DejaVu Sans Mono")
+            .unwrap();
+        assert!(mono.flags.contains(&Flag::Monospace));
+    }
+
+    #[test]
+    fn rejects_invalid_font_data() {
+        let options = FontOptions {
+            font_data: vec![Bytes::new(b"not a font".to_vec())],
+            ..Default::default()
+        };
+
+        let error = load(&options).err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("Font at index 0 is not a valid TTF, OTF or TTC font"));
+    }
+
+    #[test]
+    fn rejects_missing_font_paths() {
+        let options = FontOptions {
+            font_paths: vec![PathBuf::from("/nonexistent/typster/fonts")],
+            ..Default::default()
+        };
+
+        let error = load(&options).err().unwrap();
+        assert!(error.to_string().contains("is not a directory"));
+    }
+}
@@ -1,16 +1,44 @@
 mod convert;
+mod diagnostics;
+mod engine;
+mod fonts;
+mod metadata;
 mod packages;
+mod pdf;
+mod sandbox;
 mod world;
 
 use rustler::types::Binary;
-use rustler::{Env, Error as RustlerError, NifStruct, OwnedBinary, Term};
+use rustler::{
+    Encoder, Env, Error as RustlerError, NifMap, NifStruct, OwnedBinary, ResourceArc, Term,
+};
 use std::collections::HashMap;
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use typst::diag::Warned;
+use typst::foundations::{Bytes, Dict, Smart};
+use typst::layout::PageRanges;
 use typst::utils::Scalar;
-use typst_layout::PagedDocument;
-
+use typst_layout::{Page, PagedDocument};
+
+use diagnostics::Diagnostic;
+use engine::{Engine, EngineConfig, EngineResource};
+use fonts::{FontDescription, FontOptions};
+use metadata::Metadata;
+use packages::PackageSource;
+use sandbox::{DenialReason, FileLimits, SandboxMode};
 use world::TypstWorld;
 
+rustler::atoms! {
+    ok,
+    access_denied,
+}
+
+/// Number of renders, across all engines, the results memoized by the compiler
+/// are kept for without being used
+const MAX_MEMO_AGE: usize = 10;
+
 /// Custom error type for Typster operations
 #[derive(Debug)]
 pub enum TypstError {
@@ -25,11 +53,68 @@ pub enum TypstError {
 #[derive(NifStruct)]
 #[module = "Typster.Native.TypsterOptions"]
 struct TypsterOptions<'a> {
-    metadata: HashMap<String, String>,
+    creation_timestamp: Option<Term<'a>>,
+    document_id: Option<String>,
+    engine: Option<ResourceArc<EngineResource>>,
+    engine_options: EngineOptions<'a>,
+    files: HashMap<String, Binary<'a>>,
+    metadata: HashMap<String, Term<'a>>,
+    pages: Option<Vec<Term<'a>>>,
+    pdf_standards: Vec<String>,
     pixel_per_pt: f32,
+    sys_inputs: bool,
+    today: Option<Term<'a>>,
+    variables: Term<'a>,
+}
+
+/// Options for creating an engine, either shared between renders or
+/// built for a single render
+#[derive(NifStruct)]
+#[module = "Typster.Native.EngineOptions"]
+struct EngineOptions<'a> {
+    fonts: Vec<Binary<'a>>,
+    font_paths: Vec<String>,
+    ignore_system_fonts: bool,
     package_paths: Vec<String>,
+    package_cache_dir: Option<String>,
+    package_registry: Option<String>,
+    package_namespaces: HashMap<String, PackageSource>,
+    package_hashes: HashMap<String, String>,
     root_path: String,
-    variables: Term<'a>,
+    sandbox: SandboxMode,
+    allowed_extensions: Option<Vec<String>>,
+    max_file_size: Option<u64>,
+    max_total_size: Option<u64>,
+    offline: bool,
+}
+
+impl EngineOptions<'_> {
+    fn to_config(&self) -> EngineConfig {
+        EngineConfig {
+            root: PathBuf::from(&self.root_path),
+            package_paths: self.package_paths.iter().map(PathBuf::from).collect(),
+            package_cache_dir: self.package_cache_dir.as_ref().map(PathBuf::from),
+            registry: self.package_registry.clone(),
+            namespaces: self.package_namespaces.clone(),
+            package_hashes: self.package_hashes.clone(),
+            fonts: FontOptions {
+                font_data: self
+                    .fonts
+                    .iter()
+                    .map(|font| Bytes::new(font.as_slice().to_vec()))
+                    .collect(),
+                font_paths: self.font_paths.iter().map(PathBuf::from).collect(),
+                ignore_system_fonts: self.ignore_system_fonts,
+            },
+            sandbox: self.sandbox,
+            limits: FileLimits {
+                allowed_extensions: self.allowed_extensions.clone(),
+                max_file_size: self.max_file_size,
+                max_total_size: self.max_total_size,
+            },
+            offline: self.offline,
+        }
+    }
 }
 
 impl fmt::Display for TypstError {
@@ -73,55 +158,39 @@ impl From<RustlerError> for TypstError {
 /// Result type alias for Typster operations
 pub type TypstResult<T> = Result<T, TypstError>;
 
-/// Generate a #set document() statement from metadata map
-fn generate_document_metadata(metadata: std::collections::HashMap<String, String>) -> String {
-    if metadata.is_empty() {
-        return String::new();
-    }
-
-    let mut parts = Vec::new();
-
-    if let Some(title) = metadata.get("title") {
-        parts.push(format!("title: \"{}\"", title.replace('\\', "\\\\").replace('"', "\\\"")));
-    }
-
-    if let Some(author) = metadata.get("author") {
-        parts.push(format!("author: \"{}\"", author.replace('\\', "\\\\").replace('"', "\\\"")));
-    }
-
-    if let Some(description) = metadata.get("description") {
-        parts.push(format!(
-            "description: \"{}\"",
-            description.replace('\\', "\\\\").replace('"', "\\\"")
-        ));
-    }
+/// Error returned to Elixir by the compilation NIFs
+pub enum NifError {
+    /// A plain error message, e.g. for invalid options or variables
+    Message(String),
+    /// Structured diagnostics produced by the Typst compiler or exporters
+    Diagnostics(Vec<Diagnostic>),
+    /// Compilation failed because the sandbox refused to read a file
+    AccessDenied(AccessDenied),
+}
 
-    if let Some(keywords) = metadata.get("keywords") {
-        // Keywords can be comma-separated string
-        let keywords_list: Vec<String> = keywords
-            .split(',')
-            .map(|k| format!("\"{}\"", k.trim().replace('\\', "\\\\").replace('"', "\\\"")))
-            .collect();
-        parts.push(format!("keywords: ({})", keywords_list.join(", ")));
-    }
+/// Details of a file read refused by the sandbox, with the compiler's
+/// diagnostics for the failed compilation
+#[derive(NifMap)]
+pub struct AccessDenied {
+    message: String,
+    path: String,
+    reason: DenialReason,
+    diagnostics: Vec<Diagnostic>,
+}
 
-    if let Some(date) = metadata.get("date") {
-        let date_lower = date.to_lowercase();
-        if date_lower == "auto" {
-            parts.push("date: auto".to_string());
-        } else if date_lower == "none" {
-            parts.push("date: none".to_string());
-        } else {
-            // Assume it's a datetime string - for now, just skip it
-            // Full datetime support would require parsing the format
-            // Users can set the date directly in the template if needed
+impl Encoder for NifError {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            NifError::Message(message) => message.encode(env),
+            NifError::Diagnostics(diagnostics) => diagnostics.encode(env),
+            NifError::AccessDenied(details) => (access_denied(), details).encode(env),
         }
     }
+}
 
-    if parts.is_empty() {
-        String::new()
-    } else {
-        format!("#set document({})\n", parts.join(", "))
+impl From<String> for NifError {
+    fn from(message: String) -> Self {
+        NifError::Message(message)
     }
 }
 
@@ -129,127 +198,268 @@ fn world_from_options<'a>(
     env: Env<'a>,
     source: String,
     options: &TypsterOptions<'a>,
+    metadata: &Metadata,
 ) -> TypstResult<TypstWorld> {
-    // Generate metadata statement
-    let metadata_stmt = generate_document_metadata(options.metadata.clone());
-
     // Convert Elixir variables to Typst Dict
-    let var_dict = convert::terms_to_dict(env, options.variables.clone())
+    let var_dict = convert::terms_to_dict(env, options.variables)
         .map_err(|e| TypstError::InvalidInput(format!("Failed to convert variables: {}", e)))?;
 
-    // Convert package path strings to PathBufs
-    let paths: Vec<std::path::PathBuf> = options
-        .package_paths
-        .iter()
-        .map(|p| std::path::PathBuf::from(p))
-        .collect();
+    // Reuse the caller's engine, or build a throwaway one for this render
+    let engine = match &options.engine {
+        Some(resource) => resource.0.clone(),
+        None => Arc::new(Engine::new(options.engine_options.to_config())?),
+    };
 
-    // Prepend metadata to source
-    let full_source = if metadata_stmt.is_empty() {
-        source
+    // Deliver the variables either as top-level bindings or through `sys.inputs`
+    let (variables, inputs) = if options.sys_inputs {
+        (Dict::new(), var_dict)
     } else {
-        format!("{}{}", metadata_stmt, source)
+        (var_dict, Dict::new())
     };
 
-    // Convert root path string to PathBuf
-    let root_path = std::path::PathBuf::from(options.root_path.clone());
-
-    // Create the world with the full source code, variables, and package paths
-    let world = TypstWorld::new(full_source, var_dict, paths, root_path)
+    // Create the world with the engine, source code and variables
+    let mut world = TypstWorld::new(engine, source, variables, inputs)
         .map_err(|e| TypstError::CompileError(format!("Failed to create world: {}", e)))?;
 
+    if let Some(today) = options.today {
+        world.set_today(convert::term_to_datetime(today, "today")?);
+    }
+
+    // Let the template see the metadata through `document`, as if it started
+    // with a `#set document(...)` rule
+    world.add_styles(metadata.styles());
+
+    // Serve the files passed in memory before the files on disk
+    for (path, data) in &options.files {
+        world.add_file(path, Bytes::new(data.as_slice().to_vec()))?;
+    }
+
     Ok(world)
 }
 
+/// Compile the world into a paged document along with any warnings
+///
+/// On failure the returned diagnostics contain the errors followed by the warnings.
+/// If the failure was caused by the sandbox refusing to read a file, the
+/// denied path is reported along with the diagnostics.
+fn compile_document(world: &TypstWorld) -> Result<(PagedDocument, Vec<Diagnostic>), NifError> {
+    let Warned { output, warnings } = typst::compile(world);
+    world.engine().evict();
+    // The compiler's memoization cache is shared by the whole OS process, so
+    // it ages with every render rather than with the renders of one engine
+    comemo::evict(MAX_MEMO_AGE);
+    let warnings = diagnostics::convert(world, &warnings);
+
+    match output {
+        Ok(document) => Ok((document, warnings)),
+        Err(errors) => {
+            let mut all = diagnostics::convert(world, &errors);
+            all.extend(warnings);
+
+            match world.access_denial() {
+                Some(denial) => Err(NifError::AccessDenied(AccessDenied {
+                    message: denial.to_string(),
+                    path: denial.path,
+                    reason: denial.reason,
+                    diagnostics: all,
+                })),
+                None => Err(NifError::Diagnostics(all)),
+            }
+        }
+    }
+}
+
+/// Resolve the `:pages` option of a render
+fn page_ranges(options: &TypsterOptions) -> Result<Option<PageRanges>, NifError> {
+    options
+        .pages
+        .as_deref()
+        .map(convert::terms_to_page_ranges)
+        .transpose()
+        .map_err(|e| NifError::Message(e.to_string()))
+}
+
+/// The pages of the document selected by the `:pages` option, or all pages
+/// if it isn't given
+///
+/// Selecting none of the document's pages is an error rather than an empty
+/// result, since it usually means the page numbers are off.
+fn selected_pages<'d>(
+    document: &'d PagedDocument,
+    ranges: Option<&PageRanges>,
+) -> Result<Vec<&'d Page>, NifError> {
+    let pages: Vec<&Page> = document
+        .pages()
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| ranges.is_none_or(|ranges| ranges.includes_page_index(*index)))
+        .map(|(_, page)| page)
+        .collect();
+
+    if pages.is_empty() && !document.pages().is_empty() {
+        return Err(NifError::Message(format!(
+            "The pages option selects none of the document's {} pages",
+            document.pages().len()
+        )));
+    }
+
+    Ok(pages)
+}
+
 // Placeholder NIF function - will be replaced with actual implementation
 #[rustler::nif]
 fn test_nif() -> String {
     "Typster NIF loaded successfully".to_string()
 }
 
+/// Create an engine holding the fonts, library and file cache so they can be
+/// reused across renders
+#[rustler::nif]
+fn new_engine(options: EngineOptions<'_>) -> Result<ResourceArc<EngineResource>, String> {
+    let engine =
+        Engine::new(options.to_config()).map_err(|e| format!("Failed to create engine: {}", e))?;
+
+    Ok(ResourceArc::new(EngineResource(Arc::new(engine))))
+}
+
+/// Rescan the system fonts used by renders and engines created from now on
+#[rustler::nif]
+fn refresh_fonts() -> rustler::Atom {
+    fonts::refresh();
+    ok()
+}
+
+/// List the fonts available to documents rendered with the engine, or with
+/// the given options if there is no engine
+#[rustler::nif]
+fn list_fonts(
+    engine: Option<ResourceArc<EngineResource>>,
+    options: EngineOptions<'_>,
+) -> Result<Vec<FontDescription>, String> {
+    let fonts = match engine {
+        Some(resource) => resource.0.fonts().clone(),
+        None => fonts::load(&options.to_config().fonts)
+            .map_err(|e| format!("Failed to load fonts: {}", e))?,
+    };
+
+    Ok(fonts::describe(&fonts))
+}
+
 /// Compile a Typst template to PDF with options
+/// Returns the PDF along with any warnings reported by the compiler
 #[rustler::nif]
 fn compile_to_pdf<'a>(
     env: Env<'a>,
     source: String,
     options: TypsterOptions<'a>,
-) -> Result<Binary<'a>, String> {
+) -> Result<(Binary<'a>, Vec<Diagnostic>), NifError> {
+    let metadata = Metadata::from_terms(&options.metadata).map_err(|e| e.to_string())?;
+    let standards = pdf::standards(&options.pdf_standards)?;
+    let page_ranges = page_ranges(&options)?;
+    let timestamp = options
+        .creation_timestamp
+        .map(|term| convert::term_to_datetime(term, "creation_timestamp").and_then(pdf::timestamp))
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
     // Create the world with the source code and options
-    let world = world_from_options(env, source, &options)
+    let world = world_from_options(env, source, &options, &metadata)
         .map_err(|e| format!("Failed to create world: {}", e))?;
 
-    // Compile the document
-    let document = typst::compile(&world).output.map_err(|errors| {
-        let error_messages: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
-        format!("Compilation failed: {}", error_messages.join(", "))
-    })?;
-
-    // Render to PDF with default options
-    let pdf_options = typst_pdf::PdfOptions::default();
-    let pdf_bytes = typst_pdf::pdf(&document, &pdf_options).map_err(|errors| {
-        let error_messages: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
-        format!("PDF generation failed: {}", error_messages.join(", "))
-    })?;
+    // Compile the document, then let the metadata option override the
+    // metadata the template set itself, which takes precedence over the
+    // document styles the metadata was also given as
+    let (mut document, warnings) = compile_document(&world)?;
+    metadata.apply(document.info_mut());
+    selected_pages(&document, page_ranges.as_ref())?;
+
+    let pdf_options = typst_pdf::PdfOptions {
+        ident: match &options.document_id {
+            Some(id) => Smart::Custom(id.clone()),
+            None => Smart::Auto,
+        },
+        timestamp,
+        // Like `typst compile --pages`, export untagged PDFs when selecting
+        // pages, since typst-pdf can't tag a subset of the document
+        tagged: page_ranges.is_none(),
+        page_ranges,
+        creator: match metadata.creator {
+            Some(creator) => Smart::Custom(Some(creator)),
+            None => Smart::Auto,
+        },
+        standards,
+        ..typst_pdf::PdfOptions::default()
+    };
+    let mut pdf_bytes = typst_pdf::pdf(&document, &pdf_options)
+        .map_err(|errors| NifError::Diagnostics(diagnostics::convert(&world, &errors)))?;
+    if let Some(xmp) = &metadata.xmp {
+        pdf::add_xmp(&mut pdf_bytes, xmp).map_err(|e| e.to_string())?;
+    }
 
     // Convert Vec<u8> to Binary
     let mut binary = OwnedBinary::new(pdf_bytes.len()).unwrap();
     binary.as_mut_slice().copy_from_slice(&pdf_bytes);
 
-    Ok(binary.release(env))
+    Ok((binary.release(env), warnings))
 }
 
 /// Compile a Typst template to SVG
+/// Returns one SVG per page along with any warnings reported by the compiler
 #[rustler::nif]
 fn compile_to_svg<'a>(
     env: Env<'a>,
     source: String,
     options: TypsterOptions<'a>,
-) -> Result<Vec<String>, String> {
+) -> Result<(Vec<String>, Vec<Diagnostic>), NifError> {
+    let page_ranges = page_ranges(&options)?;
+
+    let metadata = Metadata::from_terms(&options.metadata).map_err(|e| e.to_string())?;
+
     // Create the world with the source code and options
-    let world = world_from_options(env, source, &options)
+    let world = world_from_options(env, source, &options, &metadata)
         .map_err(|e| format!("Failed to create world: {}", e))?;
 
     // Compile the document
-    let document: PagedDocument = typst::compile(&world).output.map_err(|errors| {
-        let error_messages: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
-        format!("Compilation failed: {}", error_messages.join(", "))
-    })?;
+    let (document, warnings) = compile_document(&world)?;
 
-    // Render each page to SVG
+    // Render each selected page to SVG
     let svg_options = typst_svg::SvgOptions::default();
     let mut svg_pages = Vec::new();
-    for page in document.pages().iter() {
+    for page in selected_pages(&document, page_ranges.as_ref())? {
         let svg = typst_svg::svg(page, &svg_options);
         svg_pages.push(svg);
     }
 
-    Ok(svg_pages)
+    Ok((svg_pages, warnings))
 }
 
 /// Compile a Typst template to PNG with options
+/// Returns one PNG per page along with any warnings reported by the compiler
 #[rustler::nif]
 fn compile_to_png<'a>(
     env: Env<'a>,
     source: String,
     options: TypsterOptions<'a>,
-) -> Result<Vec<Binary<'a>>, String> {
+) -> Result<(Vec<Binary<'a>>, Vec<Diagnostic>), NifError> {
+    let page_ranges = page_ranges(&options)?;
+
+    let metadata = Metadata::from_terms(&options.metadata).map_err(|e| e.to_string())?;
+
     // Create the world with the source code and options
-    let world = world_from_options(env, source, &options)
+    let world = world_from_options(env, source, &options, &metadata)
         .map_err(|e| format!("Failed to create world: {}", e))?;
 
     // Compile the document
-    let document: PagedDocument = typst::compile(&world).output.map_err(|errors| {
-        let error_messages: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
-        format!("Compilation failed: {}", error_messages.join(", "))
-    })?;
+    let (document, warnings) = compile_document(&world)?;
 
-    // Render each page to PNG
+    // Render each selected page to PNG, skipping the rasterization of the
+    // pages that weren't asked for
     let render_options = typst_render::RenderOptions {
         pixel_per_pt: Scalar::new(options.pixel_per_pt as f64),
         ..Default::default()
     };
     let mut png_pages = Vec::new();
-    for page in document.pages().iter() {
+    for page in selected_pages(&document, page_ranges.as_ref())? {
         let pixmap = typst_render::render(page, &render_options);
         let png_bytes = pixmap
             .encode_png()
@@ -261,29 +471,29 @@ fn compile_to_png<'a>(
         png_pages.push(binary.release(env));
     }
 
-    Ok(png_pages)
+    Ok((png_pages, warnings))
 }
 
 /// Check the syntax of a Typst template without rendering
-/// Returns a list of error messages if compilation fails, or an empty list if successful
+/// Returns the errors and warnings reported by the compiler, or an empty list if there are none
 #[rustler::nif]
 fn check_syntax<'a>(
     env: Env<'a>,
     source: String,
     options: TypsterOptions<'a>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<Diagnostic>, NifError> {
+    let metadata = Metadata::from_terms(&options.metadata).map_err(|e| e.to_string())?;
+
     // Create the world with the source code and options
-    let world = world_from_options(env, source, &options)
+    let world = world_from_options(env, source, &options, &metadata)
         .map_err(|e| format!("Failed to create world: {}", e))?;
 
     // Attempt to compile the document
-    match typst::compile::<PagedDocument>(&world).output {
-        Ok(_) => Ok(Vec::new()), // Success - return empty list
-        Err(errors) => {
-            // Extract error messages
-            let error_messages: Vec<String> = errors.iter().map(|e| format!("{:?}", e)).collect();
-            Ok(error_messages) // Return list of errors
-        }
+    match compile_document(&world) {
+        Ok((_, warnings)) => Ok(warnings), // Success - return any warnings
+        Err(NifError::Diagnostics(diagnostics)) => Ok(diagnostics), // Return errors and warnings
+        Err(NifError::AccessDenied(details)) => Ok(details.diagnostics),
+        Err(error) => Err(error),
     }
 }
 
@@ -0,0 +1,363 @@
+use std::collections::HashMap;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use rustler::Term;
+use typst::ecow::EcoString;
+use typst::foundations::{Datetime, OneOrMultiple, Smart, Styles};
+use typst::model::{DocumentElem, DocumentInfo};
+use typst::text::{Lang, Locale, Region, TextElem};
+
+use crate::{TypstError, TypstResult};
+
+/// Keys accepted in the `:metadata` option
+const KEYS: &[&str] = &[
+    "title",
+    "author",
+    "description",
+    "keywords",
+    "date",
+    "language",
+    "creator",
+    "xmp",
+];
+
+/// Document metadata passed with the `:metadata` option
+///
+/// It is applied to the compiled document, so every field that is set takes
+/// precedence over the template's `#set document(...)` and `#set text(lang:)`
+/// rules, while fields that aren't set keep the values from the template.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<Vec<String>>,
+    pub description: Option<String>,
+    pub keywords: Option<Vec<String>>,
+    pub date: Option<Smart<Option<Datetime>>>,
+    pub language: Option<Locale>,
+    /// The application that created the document, written as the PDF's
+    /// `/Creator` instead of "Typst"
+    pub creator: Option<String>,
+    /// `rdf:Description` elements added to the PDF's XMP metadata packet
+    pub xmp: Option<String>,
+}
+
+impl Metadata {
+    /// Read the metadata from the map passed from Elixir
+    pub fn from_terms(terms: &HashMap<String, Term>) -> TypstResult<Self> {
+        let mut metadata = Self::default();
+
+        for (key, term) in terms {
+            match key.as_str() {
+                "title" => metadata.title = Some(decode_string(key, *term)?),
+                "author" => metadata.author = Some(decode_list(key, *term)?),
+                "description" => metadata.description = Some(decode_string(key, *term)?),
+                "keywords" => {
+                    // Keywords may also be given as a comma-separated string
+                    metadata.keywords = Some(match term.decode::<String>() {
+                        Ok(keywords) => split_keywords(&keywords),
+                        Err(_) => decode_list(key, *term)?,
+                    })
+                }
+                "date" => metadata.date = Some(parse_date(&decode_string(key, *term)?)?),
+                "language" => {
+                    metadata.language = Some(parse_language(&decode_string(key, *term)?)?)
+                }
+                "creator" => metadata.creator = Some(decode_string(key, *term)?),
+                "xmp" => metadata.xmp = Some(parse_xmp(&decode_string(key, *term)?)?),
+                _ => {
+                    return Err(TypstError::InvalidInput(format!(
+                        "Unknown metadata key '{}'. Supported keys: {}",
+                        key,
+                        KEYS.join(", ")
+                    )))
+                }
+            }
+        }
+
+        Ok(metadata)
+    }
+
+    /// The `document` properties the template starts with
+    ///
+    /// Templates see them through `document.title`, `#title()` and the like,
+    /// unless they set the fields themselves. The language and creator aren't
+    /// included: setting the text language would change how the document is
+    /// laid out, and the creator isn't a document property.
+    pub fn styles(&self) -> Styles {
+        let mut styles = Styles::new();
+        if let Some(title) = &self.title {
+            styles.set(DocumentElem::title, Some(TextElem::packed(title.as_str())));
+        }
+        if let Some(author) = &self.author {
+            styles.set(DocumentElem::author, OneOrMultiple(to_strs(author)));
+        }
+        if let Some(description) = &self.description {
+            styles.set(
+                DocumentElem::description,
+                Some(TextElem::packed(description.as_str())),
+            );
+        }
+        if let Some(keywords) = &self.keywords {
+            styles.set(DocumentElem::keywords, OneOrMultiple(to_strs(keywords)));
+        }
+        if let Some(date) = self.date {
+            styles.set(DocumentElem::date, date);
+        }
+        styles
+    }
+
+    /// Overwrite the document's metadata with the fields that are set
+    ///
+    /// The template's own `#set document(...)` rules take precedence over
+    /// [`styles`](Self::styles), so this is what makes the option win in the
+    /// PDF. The language is only applied here.
+    pub fn apply(&self, info: &mut DocumentInfo) {
+        if let Some(title) = &self.title {
+            info.title = Some(title.as_str().into());
+        }
+        if let Some(author) = &self.author {
+            info.author = to_strs(author);
+        }
+        if let Some(description) = &self.description {
+            info.description = Some(description.as_str().into());
+        }
+        if let Some(keywords) = &self.keywords {
+            info.keywords = to_strs(keywords);
+        }
+        if let Some(date) = self.date {
+            info.date = date;
+        }
+        if let Some(language) = self.language {
+            info.locale = Smart::Custom(language);
+        }
+    }
+}
+
+fn to_strs(values: &[String]) -> Vec<EcoString> {
+    values.iter().map(|value| value.as_str().into()).collect()
+}
+
+fn decode_string(key: &str, term: Term) -> TypstResult<String> {
+    term.decode()
+        .map_err(|_| TypstError::InvalidInput(format!("Metadata '{}' must be a string", key)))
+}
+
+fn decode_list(key: &str, term: Term) -> TypstResult<Vec<String>> {
+    term.decode::<String>()
+        .map(|value| vec![value])
+        .or_else(|_| term.decode::<Vec<String>>())
+        .map_err(|_| {
+            TypstError::InvalidInput(format!(
+                "Metadata '{}' must be a string or a list of strings",
+                key
+            ))
+        })
+}
+
+fn split_keywords(keywords: &str) -> Vec<String> {
+    keywords
+        .split(',')
+        .map(|keyword| keyword.trim().to_string())
+        .filter(|keyword| !keyword.is_empty())
+        .collect()
+}
+
+/// Check that custom XMP holds elements to add to the packet typst-pdf
+/// writes, rather than a packet of its own
+pub fn parse_xmp(xmp: &str) -> TypstResult<String> {
+    let xmp = xmp.trim();
+    if !xmp.starts_with('<')
+        || ["<?xpacket", "x:xmpmeta", "rdf:RDF"]
+            .iter()
+            .any(|tag| xmp.contains(tag))
+    {
+        return Err(TypstError::InvalidInput(
+            "Metadata 'xmp' must be rdf:Description elements, which are added to the \
+             XMP packet written for the document, not a packet of its own"
+                .to_string(),
+        ));
+    }
+
+    Ok(xmp.to_string())
+}
+
+/// Parse `"auto"`, `"none"` or an ISO 8601 date or datetime
+///
+/// Datetimes with a UTC offset (as produced for Elixir `DateTime`s) are
+/// converted to UTC; datetimes without one are used as they are.
+pub fn parse_date(date: &str) -> TypstResult<Smart<Option<Datetime>>> {
+    let date = date.trim();
+    let invalid = || {
+        TypstError::InvalidInput(format!(
+            "Invalid metadata date '{}': expected \"auto\", \"none\" or an ISO 8601 date or datetime",
+            date
+        ))
+    };
+
+    match date.to_lowercase().as_str() {
+        "auto" => return Ok(Smart::Auto),
+        "none" => return Ok(Smart::Custom(None)),
+        _ => {}
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        let datetime = Datetime::from_ymd(date.year(), date.month() as u8, date.day() as u8)
+            .ok_or_else(invalid)?;
+        return Ok(Smart::Custom(Some(datetime)));
+    }
+
+    let datetime = chrono::DateTime::parse_from_rfc3339(date)
+        .map(|datetime| datetime.naive_utc())
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f"))
+        .or_else(|_| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S%.f"))
+        .map_err(|_| invalid())?;
+
+    let datetime = Datetime::from_ymd_hms(
+        datetime.year(),
+        datetime.month() as u8,
+        datetime.day() as u8,
+        datetime.hour() as u8,
+        datetime.minute() as u8,
+        datetime.second() as u8,
+    )
+    .ok_or_else(invalid)?;
+
+    Ok(Smart::Custom(Some(datetime)))
+}
+
+/// Parse a language code with an optional region, e.g. `"en"` or `"en-US"`
+pub fn parse_language(language: &str) -> TypstResult<Locale> {
+    let (lang, region) = match language.split_once(['-', '_']) {
+        Some((lang, region)) => (lang, Some(region)),
+        None => (language, None),
+    };
+
+    let invalid = |reason: &str| {
+        TypstError::InvalidInput(format!(
+            "Invalid metadata language '{}': {}",
+            language, reason
+        ))
+    };
+
+    Ok(Locale {
+        lang: lang.parse::<Lang>().map_err(invalid)?,
+        region: region
+            .map(|region| region.parse::<Region>())
+            .transpose()
+            .map_err(invalid)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, EngineConfig};
+    use crate::world::TypstWorld;
+    use std::sync::Arc;
+    use typst::foundations::Dict;
+    use typst::model::Document;
+    use typst_layout::PagedDocument;
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("auto").unwrap(), Smart::Auto);
+        assert_eq!(parse_date("None").unwrap(), Smart::Custom(None));
+        assert_eq!(
+            parse_date("2024-02-29").unwrap(),
+            Smart::Custom(Datetime::from_ymd(2024, 2, 29))
+        );
+        assert_eq!(
+            parse_date("2024-03-01T02:30:00+03:00").unwrap(),
+            Smart::Custom(Datetime::from_ymd_hms(2024, 2, 29, 23, 30, 0))
+        );
+        assert_eq!(
+            parse_date("2024-02-29T23:30:15.123456").unwrap(),
+            Smart::Custom(Datetime::from_ymd_hms(2024, 2, 29, 23, 30, 15))
+        );
+
+        let error = parse_date("29/02/2024").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid metadata date '29/02/2024'"));
+    }
+
+    #[test]
+    fn accepts_only_descriptions_as_xmp() {
+        let description = r#"<rdf:Description rdf:about="" xmlns:fx="urn:factur-x"/>"#;
+        assert_eq!(
+            parse_xmp(&format!(" {}\n", description)).unwrap(),
+            description
+        );
+
+        let packet = format!("<x:xmpmeta><rdf:RDF>{}</rdf:RDF></x:xmpmeta>", description);
+        assert!(parse_xmp(&packet).is_err());
+        assert!(parse_xmp("fx:DocumentType=INVOICE").is_err());
+    }
+
+    #[test]
+    fn parses_languages() {
+        let locale = parse_language("de-AT").unwrap();
+        assert_eq!(locale.lang, Lang::GERMAN);
+        assert_eq!(locale.region, Some("AT".parse().unwrap()));
+        assert_eq!(parse_language("fr").unwrap().region, None);
+
+        let error = parse_language("english").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid metadata language 'english'"));
+    }
+
+    #[test]
+    fn overrides_only_the_fields_that_are_set() {
+        let mut info = DocumentInfo {
+            title: Some("From the template".into()),
+            description: Some("Kept".into()),
+            ..DocumentInfo::default()
+        };
+
+        let metadata = Metadata {
+            title: Some("From the options".to_string()),
+            author: Some(vec!["Ann".to_string(), "Bob".to_string()]),
+            keywords: Some(split_keywords("invoice, 2025,")),
+            ..Metadata::default()
+        };
+        metadata.apply(&mut info);
+
+        assert_eq!(info.title.as_deref(), Some("From the options"));
+        assert_eq!(info.description.as_deref(), Some("Kept"));
+        assert_eq!(info.author, ["Ann", "Bob"]);
+        assert_eq!(info.keywords, ["invoice", "2025"]);
+        assert_eq!(info.date, Smart::Auto);
+    }
+
+    fn compile(source: &str, metadata: &Metadata) -> PagedDocument {
+        let engine = Arc::new(Engine::new(EngineConfig::default()).unwrap());
+        let mut world =
+            TypstWorld::new(engine, source.to_string(), Dict::new(), Dict::new()).unwrap();
+        world.add_styles(metadata.styles());
+        typst::compile::<PagedDocument>(&world).output.unwrap()
+    }
+
+    #[test]
+    fn exposes_the_fields_to_the_template() {
+        let metadata = Metadata {
+            title: Some("Quarterly".to_string()),
+            author: Some(vec!["Ann".to_string()]),
+            ..Metadata::default()
+        };
+
+        let source = "#context assert.eq(document.title.text, \"Quarterly\")\n\
+                      #context assert.eq(document.author, (\"Ann\",))";
+        let document = compile(source, &metadata);
+        assert_eq!(document.info().title.as_deref(), Some("Quarterly"));
+
+        // The template's own rules win inside the template, but not in the PDF
+        let source = "#set document(title: \"Template\")\n\
+                      #context assert.eq(document.title.text, \"Template\")";
+        let mut document = compile(source, &metadata);
+        assert_eq!(document.info().title.as_deref(), Some("Template"));
+
+        metadata.apply(document.info_mut());
+        assert_eq!(document.info().title.as_deref(), Some("Quarterly"));
+    }
+}
@@ -1,17 +1,238 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use flate2::read::GzDecoder;
+use rustler::NifTaggedEnum;
+use sha2::{Digest, Sha256};
 use tar::Archive;
 use typst::syntax::package::PackageSpec;
 
 use crate::TypstError;
 
 /// The base URL for the Typst package registry
-const PACKAGE_REGISTRY_URL: &str = "https://packages.typst.org";
+pub const PACKAGE_REGISTRY_URL: &str = "https://packages.typst.org";
 
-/// Download and extract a package from the Typst registry
-pub fn download_package(spec: &PackageSpec, cache_dir: &Path) -> Result<PathBuf, TypstError> {
+/// Where package archives are downloaded from
+///
+/// Archives are looked up as `{namespace}/{name}-{version}.tar.gz` below the
+/// registry, the layout of the official registry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Registry {
+    /// An HTTP(S) server such as the official registry or a mirror of it
+    Http(String),
+    /// A local directory with the same layout, given as a `file://` URL
+    Directory(PathBuf),
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::Http(PACKAGE_REGISTRY_URL.to_string())
+    }
+}
+
+impl Registry {
+    /// Parse an `http://`, `https://` or `file://` registry URL
+    pub fn from_url(url: &str) -> Result<Self, TypstError> {
+        let url = url.trim_end_matches('/');
+
+        if let Some(path) = url.strip_prefix("file://") {
+            Ok(Registry::Directory(PathBuf::from(path)))
+        } else if url.starts_with("http://") || url.starts_with("https://") {
+            Ok(Registry::Http(url.to_string()))
+        } else {
+            Err(TypstError::InvalidInput(format!(
+                "Invalid package registry URL '{}': expected an http://, https:// or file:// URL",
+                url
+            )))
+        }
+    }
+
+    /// The source of the packages in `namespace`
+    pub fn source(&self, namespace: &str) -> PackageSource {
+        match self {
+            Registry::Http(url) => PackageSource::Http {
+                url: format!("{}/{}", url, namespace),
+                headers: HashMap::new(),
+            },
+            Registry::Directory(dir) => {
+                PackageSource::Archives(dir.join(namespace).to_string_lossy().into_owned())
+            }
+        }
+    }
+}
+
+/// Where the packages of one namespace come from, encoded as
+/// `{:directory, path}`, `{:archives, path}` or `{:http, %{url: url, headers: headers}}`
+#[derive(NifTaggedEnum, Debug, Clone, PartialEq, Eq)]
+pub enum PackageSource {
+    /// Unpacked packages laid out as `{name}/{version}`, used in place
+    Directory(String),
+    /// `{name}-{version}.tar.gz` archives in a local directory
+    Archives(String),
+    /// `{name}-{version}.tar.gz` archives below an HTTP(S) URL, requested
+    /// with the given headers (e.g. `authorization`)
+    Http {
+        url: String,
+        headers: HashMap<String, String>,
+    },
+}
+
+impl PackageSource {
+    /// The directory of an unpacked package, for sources that don't have to
+    /// be downloaded
+    pub fn local_dir(&self, spec: &PackageSpec) -> Option<PathBuf> {
+        match self {
+            PackageSource::Directory(dir) => Some(
+                Path::new(dir)
+                    .join(spec.name.as_str())
+                    .join(spec.version.to_string()),
+            ),
+            PackageSource::Archives(_) | PackageSource::Http { .. } => None,
+        }
+    }
+
+    /// Fetch the `.tar.gz` archive of a package
+    fn fetch(&self, spec: &PackageSpec) -> Result<Vec<u8>, TypstError> {
+        let archive = format!("{}-{}.tar.gz", spec.name.as_str(), spec.version);
+
+        match self {
+            PackageSource::Directory(dir) => Err(TypstError::PackageError(format!(
+                "Package {} is not in {}",
+                spec, dir
+            ))),
+            PackageSource::Archives(dir) => {
+                let path = Path::new(dir).join(&archive);
+                fs::read(&path).map_err(|e| {
+                    TypstError::PackageError(format!(
+                        "Failed to read package archive {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })
+            }
+            PackageSource::Http { url, headers } => {
+                let url = format!("{}/{}", url.trim_end_matches('/'), archive);
+                let client = reqwest::blocking::Client::new();
+                let request = headers
+                    .iter()
+                    .fold(client.get(&url), |request, (name, value)| {
+                        request.header(name, value)
+                    });
+
+                let response = request.send().map_err(|e| {
+                    TypstError::PackageError(format!("Failed to download package: {}", e))
+                })?;
+
+                if !response.status().is_success() {
+                    return Err(TypstError::PackageError(format!(
+                        "Failed to download package {}: HTTP {}",
+                        spec,
+                        response.status()
+                    )));
+                }
+
+                let bytes = response.bytes().map_err(|e| {
+                    TypstError::PackageError(format!("Failed to read package data: {}", e))
+                })?;
+                Ok(bytes.to_vec())
+            }
+        }
+    }
+}
+
+/// Parse the SHA-256 digests package archives are pinned to, keyed by spec
+/// (e.g. `"@preview/tiaoma:0.3.0"`)
+///
+/// Digests are hexadecimal and may be prefixed with `sha256:`.
+pub fn parse_hashes(
+    hashes: &HashMap<String, String>,
+) -> Result<HashMap<PackageSpec, String>, TypstError> {
+    hashes
+        .iter()
+        .map(|(spec, hash)| {
+            let spec = spec.parse::<PackageSpec>().map_err(|e| {
+                TypstError::InvalidInput(format!("Invalid package spec '{}': {}", spec, e))
+            })?;
+
+            let hash = hash.trim();
+            let digest = hash.strip_prefix("sha256:").unwrap_or(hash).to_lowercase();
+            if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(TypstError::InvalidInput(format!(
+                    "Invalid SHA-256 digest '{}' for package {}: expected 64 hexadecimal characters",
+                    hash, spec
+                )));
+            }
+
+            Ok((spec, digest))
+        })
+        .collect()
+}
+
+/// Check a downloaded archive against the digest its package is pinned to
+fn verify(spec: &PackageSpec, bytes: &[u8], expected: &str) -> Result<(), TypstError> {
+    let actual = format!("{:x}", Sha256::digest(bytes));
+
+    if actual != expected {
+        return Err(TypstError::PackageError(format!(
+            "Package {} failed the integrity check: expected SHA-256 {}, got {}",
+            spec, expected, actual
+        )));
+    }
+
+    Ok(())
+}
+
+/// Every package has a manifest, so a package directory without one was left
+/// behind by an extraction that didn't finish
+const MANIFEST: &str = "typst.toml";
+
+/// File next to the manifest of a pinned package, holding the digest its
+/// archive was verified against
+const VERIFIED_DIGEST: &str = ".typster-sha256";
+
+/// Distinguishes the temporary directories of extractions in this process
+static EXTRACTIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether the package in `package_dir` was extracted completely
+pub fn is_installed(package_dir: &Path) -> bool {
+    package_dir.join(MANIFEST).is_file()
+}
+
+/// Whether the package in `package_dir` can be used as it is: it was
+/// extracted completely and, if it's pinned to `hash`, installed from an
+/// archive that was verified against that digest
+///
+/// Packages installed without a pin, e.g. by the Typst CLI sharing the same
+/// cache, have no verified digest, so pinned packages are never taken from
+/// the cache on trust.
+pub fn is_usable(package_dir: &Path, hash: Option<&str>) -> bool {
+    is_installed(package_dir)
+        && hash.is_none_or(|hash| {
+            fs::read_to_string(package_dir.join(VERIFIED_DIGEST))
+                .is_ok_and(|digest| digest.trim() == hash)
+        })
+}
+
+/// Download a package from its source and extract it into the cache
+///
+/// If `hash` is given, the archive must have that SHA-256 digest, which is
+/// checked before anything is extracted. The digest is recorded with the
+/// package, and a cached package without it is downloaded and verified again.
+///
+/// The archive is extracted into a temporary directory next to the package's
+/// directory, which is renamed into place once the extraction finished, so a
+/// crash never leaves a partially extracted package behind. Incomplete
+/// packages from earlier versions, and unverified ones when `hash` is given,
+/// are replaced.
+pub fn download_package(
+    spec: &PackageSpec,
+    source: &PackageSource,
+    cache_dir: &Path,
+    hash: Option<&str>,
+) -> Result<PathBuf, TypstError> {
     // Create cache directory structure: cache_dir/namespace/name/version
     let package_dir = cache_dir
         .join(spec.namespace.as_str())
@@ -19,48 +240,90 @@ pub fn download_package(spec: &PackageSpec, cache_dir: &Path) -> Result<PathBuf,
         .join(spec.version.to_string());
 
     // Check if package already exists in cache
-    if package_dir.exists() {
+    if is_usable(&package_dir, hash) {
         return Ok(package_dir);
     }
 
-    // Construct download URL
-    let package_name = format!("{}-{}", spec.name.as_str(), spec.version);
-    let url = format!(
-        "{}/{}/{}.tar.gz",
-        PACKAGE_REGISTRY_URL,
-        spec.namespace.as_str(),
-        package_name
-    );
-
-    // Download the package
-    let response = reqwest::blocking::get(&url)
-        .map_err(|e| TypstError::PackageError(format!("Failed to download package: {}", e)))?;
-
-    if !response.status().is_success() {
-        return Err(TypstError::PackageError(format!(
-            "Failed to download package {}: HTTP {}",
-            package_name,
-            response.status()
-        )));
+    let bytes = source.fetch(spec)?;
+    if let Some(hash) = hash {
+        verify(spec, &bytes, hash)?;
     }
 
-    let bytes = response
-        .bytes()
-        .map_err(|e| TypstError::PackageError(format!("Failed to read package data: {}", e)))?;
-
     // Create parent directory
-    fs::create_dir_all(package_dir.parent().unwrap())
+    let parent = package_dir.parent().unwrap();
+    fs::create_dir_all(parent)
         .map_err(|e| TypstError::IoError(format!("Failed to create cache directory: {}", e)))?;
 
-    // Extract the tar.gz
-    let decoder = GzDecoder::new(&bytes[..]);
+    // Extract the tar.gz into a temporary directory on the same file system
+    let temp_dir = parent.join(format!(
+        ".{}-{}-{}.tmp",
+        spec.version,
+        process::id(),
+        EXTRACTIONS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let result = extract(spec, &bytes, &temp_dir)
+        .and_then(|()| record_digest(&temp_dir, hash))
+        .and_then(|()| install(&temp_dir, &package_dir, hash));
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
+    result?;
+
+    Ok(package_dir)
+}
+
+fn extract(spec: &PackageSpec, bytes: &[u8], dir: &Path) -> Result<(), TypstError> {
+    let decoder = GzDecoder::new(bytes);
     let mut archive = Archive::new(decoder);
 
     archive
-        .unpack(&package_dir)
+        .unpack(dir)
         .map_err(|e| TypstError::PackageError(format!("Failed to extract package: {}", e)))?;
 
-    Ok(package_dir)
+    if !is_installed(dir) {
+        return Err(TypstError::PackageError(format!(
+            "Package {} has no {}",
+            spec, MANIFEST
+        )));
+    }
+
+    Ok(())
+}
+
+/// Record the digest a pinned package's archive was verified against
+fn record_digest(dir: &Path, hash: Option<&str>) -> Result<(), TypstError> {
+    match hash {
+        Some(hash) => fs::write(dir.join(VERIFIED_DIGEST), hash).map_err(|e| {
+            TypstError::IoError(format!("Failed to record the package digest: {}", e))
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Move an extracted package into place, replacing an incomplete one, or an
+/// unverified one if the package is pinned to `hash`
+fn install(temp_dir: &Path, package_dir: &Path, hash: Option<&str>) -> Result<(), TypstError> {
+    let failed = |e: std::io::Error| {
+        TypstError::IoError(format!(
+            "Failed to install package into {}: {}",
+            package_dir.display(),
+            e
+        ))
+    };
+
+    if package_dir.exists() && !is_usable(package_dir, hash) {
+        fs::remove_dir_all(package_dir).map_err(failed)?;
+    }
+
+    match fs::rename(temp_dir, package_dir) {
+        Ok(()) => Ok(()),
+        // Another process installed the package in the meantime
+        Err(_) if is_usable(package_dir, hash) => {
+            let _ = fs::remove_dir_all(temp_dir);
+            Ok(())
+        }
+        Err(e) => Err(failed(e)),
+    }
 }
 
 /// Get or create the default cache directory for Typst packages
@@ -100,3 +363,316 @@ mod dirs {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("typster-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A package archive containing `typst.toml` and `lib.typ`
+    fn archive(contents: &str) -> Vec<u8> {
+        archive_of(&[("typst.toml", "[package]"), ("lib.typ", contents)])
+    }
+
+    fn archive_of(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Names of the entries in `dir`
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[test]
+    fn parses_registry_urls() {
+        assert_eq!(
+            Registry::from_url("https://mirror.example.com/typst/").unwrap(),
+            Registry::Http("https://mirror.example.com/typst".to_string())
+        );
+        assert_eq!(
+            Registry::from_url("file:///srv/typst").unwrap(),
+            Registry::Directory(PathBuf::from("/srv/typst"))
+        );
+
+        let error = Registry::from_url("ftp://example.com").unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Invalid package registry URL 'ftp://example.com'"));
+    }
+
+    #[test]
+    fn downloads_from_a_registry_directory() {
+        let registry = temp_dir("registry-dir");
+        fs::create_dir_all(registry.join("preview")).unwrap();
+        fs::write(
+            registry.join("preview/hello-0.1.0.tar.gz"),
+            archive("#let hello = [Hi]"),
+        )
+        .unwrap();
+
+        let cache = temp_dir("registry-dir-cache");
+        let spec = "@preview/hello:0.1.0".parse().unwrap();
+        let url = format!("file://{}", registry.display());
+        let source = Registry::from_url(&url).unwrap().source("preview");
+        let dir = download_package(&spec, &source, &cache, None).unwrap();
+
+        assert_eq!(dir, cache.join("preview/hello/0.1.0"));
+        assert_eq!(
+            fs::read_to_string(dir.join("lib.typ")).unwrap(),
+            "#let hello = [Hi]"
+        );
+
+        let missing = "@preview/missing:0.1.0".parse().unwrap();
+        let error = download_package(&missing, &source, &cache, None).unwrap_err();
+        assert!(error.to_string().contains("missing-0.1.0.tar.gz"));
+
+        fs::remove_dir_all(&registry).unwrap();
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    /// Serve `body` to a single request, returning the server's URL and the
+    /// request it received
+    fn serve(body: Vec<u8>) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let len = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..len]).into_owned();
+
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            )
+            .unwrap();
+            stream.write_all(&body).unwrap();
+            request
+        });
+
+        (url, server)
+    }
+
+    #[test]
+    fn downloads_from_an_http_mirror() {
+        let (url, server) = serve(archive("#let hello = [Mirrored]"));
+
+        let cache = temp_dir("registry-http-cache");
+        let spec = "@preview/hello:0.1.0".parse().unwrap();
+        let source = Registry::from_url(&url).unwrap().source("preview");
+        let dir = download_package(&spec, &source, &cache, None).unwrap();
+
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /preview/hello-0.1.0.tar.gz "));
+        assert_eq!(
+            fs::read_to_string(dir.join("lib.typ")).unwrap(),
+            "#let hello = [Mirrored]"
+        );
+
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn downloads_from_a_namespace_source_with_headers() {
+        let (url, server) = serve(archive("#let hello = [Private]"));
+
+        let cache = temp_dir("namespace-http-cache");
+        let spec = "@acme/hello:1.0.0".parse().unwrap();
+        let source = PackageSource::Http {
+            url: format!("{}/typst/", url),
+            headers: HashMap::from([("Authorization".to_string(), "Bearer s3cret".to_string())]),
+        };
+        let dir = download_package(&spec, &source, &cache, None).unwrap();
+
+        let request = server.join().unwrap();
+        assert!(request.starts_with("GET /typst/hello-1.0.0.tar.gz "));
+        assert!(request
+            .to_lowercase()
+            .contains("authorization: bearer s3cret\r\n"));
+        assert_eq!(dir, cache.join("acme/hello/1.0.0"));
+
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn verifies_pinned_archives_before_extracting() {
+        let registry = temp_dir("pinned");
+        let body = archive("#let hello = [Pinned]");
+        fs::write(registry.join("hello-0.1.0.tar.gz"), &body).unwrap();
+
+        let source = PackageSource::Archives(registry.to_string_lossy().into_owned());
+        let spec: PackageSpec = "@preview/hello:0.1.0".parse().unwrap();
+        let digest = format!("{:x}", Sha256::digest(&body));
+        let hashes = HashMap::from([(spec.to_string(), format!("sha256:{}", digest))]);
+        let hashes = parse_hashes(&hashes).unwrap();
+        assert_eq!(hashes[&spec], digest);
+
+        let cache = temp_dir("pinned-cache");
+        let wrong = "0".repeat(64);
+        let error = download_package(&spec, &source, &cache, Some(&wrong)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Package error: Package @preview/hello:0.1.0 failed the integrity check: \
+                 expected SHA-256 {}, got {}",
+                wrong, digest
+            )
+        );
+        assert!(!cache.join("preview/hello/0.1.0").exists());
+
+        let dir = download_package(&spec, &source, &cache, Some(&hashes[&spec])).unwrap();
+        assert!(dir.join("lib.typ").exists());
+
+        let invalid = HashMap::from([(spec.to_string(), "abc".to_string())]);
+        assert!(parse_hashes(&invalid)
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid SHA-256 digest 'abc' for package @preview/hello:0.1.0"));
+
+        fs::remove_dir_all(&registry).unwrap();
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn verifies_pinned_packages_found_in_the_cache() {
+        let registry = temp_dir("pinned-cached");
+        let body = archive("#let hello = [Genuine]");
+        fs::write(registry.join("hello-0.1.0.tar.gz"), &body).unwrap();
+        let source = PackageSource::Archives(registry.to_string_lossy().into_owned());
+        let spec: PackageSpec = "@preview/hello:0.1.0".parse().unwrap();
+        let digest = format!("{:x}", Sha256::digest(&body));
+
+        // Installed by another tool sharing the cache, and since modified
+        let cache = temp_dir("pinned-cached-cache");
+        let package_dir = cache.join("preview/hello/0.1.0");
+        fs::create_dir_all(&package_dir).unwrap();
+        fs::write(package_dir.join("typst.toml"), "[package]").unwrap();
+        fs::write(package_dir.join("lib.typ"), "#let hello = [Tampered]").unwrap();
+        assert!(is_usable(&package_dir, None));
+        assert!(!is_usable(&package_dir, Some(&digest)));
+
+        // A pin the source doesn't match fails instead of using the cache
+        let wrong = "0".repeat(64);
+        let error = download_package(&spec, &source, &cache, Some(&wrong)).unwrap_err();
+        assert!(error.to_string().contains("failed the integrity check"));
+
+        // A matching pin replaces the unverified package
+        let dir = download_package(&spec, &source, &cache, Some(&digest)).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("lib.typ")).unwrap(),
+            "#let hello = [Genuine]"
+        );
+        assert!(is_usable(&dir, Some(&digest)));
+        assert!(!is_usable(&dir, Some(&wrong)));
+
+        // Once verified, the package is used without fetching it again
+        fs::remove_dir_all(&registry).unwrap();
+        assert_eq!(
+            download_package(&spec, &source, &cache, Some(&digest)).unwrap(),
+            dir
+        );
+
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn replaces_incomplete_installs() {
+        let registry = temp_dir("repair");
+        fs::write(
+            registry.join("hello-0.1.0.tar.gz"),
+            archive("#let hello = [Complete]"),
+        )
+        .unwrap();
+        let source = PackageSource::Archives(registry.to_string_lossy().into_owned());
+
+        // An extraction that crashed halfway, before the manifest was written
+        let cache = temp_dir("repair-cache");
+        let package_dir = cache.join("preview/hello/0.1.0");
+        fs::create_dir_all(package_dir.join("src")).unwrap();
+        fs::write(package_dir.join("src/partial.typ"), "#let").unwrap();
+        assert!(!is_installed(&package_dir));
+
+        let spec = "@preview/hello:0.1.0".parse().unwrap();
+        let dir = download_package(&spec, &source, &cache, None).unwrap();
+
+        assert!(is_installed(&dir));
+        assert_eq!(entries(&dir), ["lib.typ", "typst.toml"]);
+        assert_eq!(entries(&cache.join("preview/hello")), ["0.1.0"]);
+
+        fs::remove_dir_all(&registry).unwrap();
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn leaves_nothing_behind_when_extraction_fails() {
+        let registry = temp_dir("broken");
+        fs::write(
+            registry.join("hello-0.1.0.tar.gz"),
+            archive_of(&[("lib.typ", "#let hello = [No manifest]")]),
+        )
+        .unwrap();
+        fs::write(registry.join("truncated-0.1.0.tar.gz"), &archive("")[..20]).unwrap();
+        let source = PackageSource::Archives(registry.to_string_lossy().into_owned());
+
+        let cache = temp_dir("broken-cache");
+        let spec = "@preview/hello:0.1.0".parse().unwrap();
+        let error = download_package(&spec, &source, &cache, None).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("Package @preview/hello:0.1.0 has no typst.toml"));
+        assert!(entries(&cache.join("preview/hello")).is_empty());
+
+        let spec = "@preview/truncated:0.1.0".parse().unwrap();
+        let error = download_package(&spec, &source, &cache, None).unwrap_err();
+        assert!(error.to_string().contains("Failed to extract package"));
+        assert!(entries(&cache.join("preview/truncated")).is_empty());
+
+        fs::remove_dir_all(&registry).unwrap();
+        fs::remove_dir_all(&cache).unwrap();
+    }
+
+    #[test]
+    fn resolves_directory_sources_in_place() {
+        let spec = "@acme/hello:1.0.0".parse().unwrap();
+        let source = PackageSource::Directory("/srv/typst/acme".to_string());
+
+        assert_eq!(
+            source.local_dir(&spec),
+            Some(PathBuf::from("/srv/typst/acme/hello/1.0.0"))
+        );
+        assert_eq!(
+            PackageSource::Archives("/srv".to_string()).local_dir(&spec),
+            None
+        );
+    }
+}
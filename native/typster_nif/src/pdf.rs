@@ -0,0 +1,317 @@
+use std::io::Write;
+
+use chrono::{Datelike, Timelike};
+use typst::foundations::Datetime;
+use typst_pdf::{PdfStandard, PdfStandards, Timestamp};
+
+use crate::convert::DateValue;
+use crate::diagnostics::Diagnostic;
+use crate::{NifError, TypstError, TypstResult};
+
+/// PDF standards by the names `typst compile --pdf-standard` accepts
+const STANDARDS: &[(&str, PdfStandard)] = &[
+    ("1.4", PdfStandard::V_1_4),
+    ("1.5", PdfStandard::V_1_5),
+    ("1.6", PdfStandard::V_1_6),
+    ("1.7", PdfStandard::V_1_7),
+    ("2.0", PdfStandard::V_2_0),
+    ("a-1b", PdfStandard::A_1b),
+    ("a-1a", PdfStandard::A_1a),
+    ("a-2b", PdfStandard::A_2b),
+    ("a-2u", PdfStandard::A_2u),
+    ("a-2a", PdfStandard::A_2a),
+    ("a-3b", PdfStandard::A_3b),
+    ("a-3u", PdfStandard::A_3u),
+    ("a-3a", PdfStandard::A_3a),
+    ("a-4", PdfStandard::A_4),
+    ("a-4f", PdfStandard::A_4f),
+    ("a-4e", PdfStandard::A_4e),
+    ("ua-1", PdfStandard::Ua_1),
+];
+
+/// Look up the PDF standards the document must conform to
+///
+/// Unknown names are reported as an error message. Combinations typst-pdf
+/// can't satisfy at the same time (e.g. PDF/A-1b and PDF 2.0) are reported as
+/// a diagnostic with typst-pdf's hints, like the validation errors of the
+/// export itself.
+pub fn standards(names: &[String]) -> Result<PdfStandards, NifError> {
+    let list = names
+        .iter()
+        .map(|name| parse_standard(name))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    PdfStandards::new(&list).map_err(|error| {
+        let hints = error.hints().iter().map(|hint| hint.to_string()).collect();
+        NifError::Diagnostics(vec![Diagnostic::error(error.message().as_str(), hints)])
+    })
+}
+
+fn parse_standard(name: &str) -> Result<PdfStandard, NifError> {
+    let normalized = name.trim().to_lowercase().replace('_', "-");
+
+    STANDARDS
+        .iter()
+        .find(|(standard, _)| *standard == normalized)
+        .map(|(_, standard)| *standard)
+        .ok_or_else(|| {
+            let supported: Vec<&str> = STANDARDS.iter().map(|(name, _)| *name).collect();
+            NifError::Message(format!(
+                "Unknown PDF standard '{}'. Supported standards: {}",
+                name,
+                supported.join(", ")
+            ))
+        })
+}
+
+/// Convert the `:creation_timestamp` option to the timestamp typst-pdf writes
+/// as the creation date when the document's date is `auto`
+///
+/// Dates are written without a time; DateTimes keep their UTC offset.
+pub fn timestamp(time: DateValue) -> TypstResult<Timestamp> {
+    let invalid = || TypstError::InvalidInput("Invalid creation_timestamp".to_string());
+
+    match time {
+        DateValue::Date(date) => {
+            let datetime = Datetime::from_ymd(date.year(), date.month() as u8, date.day() as u8)
+                .ok_or_else(invalid)?;
+            Ok(Timestamp::new_utc(datetime))
+        }
+        DateValue::DateTime(time) => {
+            let datetime = Datetime::from_ymd_hms(
+                time.year(),
+                time.month() as u8,
+                time.day() as u8,
+                time.hour() as u8,
+                time.minute() as u8,
+                time.second() as u8,
+            )
+            .ok_or_else(invalid)?;
+
+            match time.offset().local_minus_utc() / 60 {
+                0 => Ok(Timestamp::new_utc(datetime)),
+                minutes => Timestamp::new_local(datetime, minutes).ok_or_else(invalid),
+            }
+        }
+    }
+}
+
+/// Add `xmp` to the XMP metadata packet of a PDF written by typst-pdf
+///
+/// typst-pdf writes the packet itself and offers no way to extend it, so the
+/// metadata stream is replaced through an incremental update: the stream is
+/// written again after the end of the file with `xmp` added to its `rdf:RDF`
+/// element, followed by a cross-reference section and trailer pointing to it.
+/// The objects typst-pdf wrote are left as they are.
+pub fn add_xmp(pdf: &mut Vec<u8>, xmp: &str) -> TypstResult<()> {
+    let missing =
+        || TypstError::RenderError("Couldn't find the XMP metadata packet of the PDF".to_string());
+
+    // The cross-reference table and trailer at the end of the file
+    let startxref = rfind(pdf, b"startxref").ok_or_else(missing)?;
+    let xref = number_after(&pdf[startxref..], b"startxref").ok_or_else(missing)?;
+    let trailer = rfind(&pdf[..startxref], b"trailer").ok_or_else(missing)? + b"trailer".len();
+    let trailer = pdf[trailer..startxref]
+        .trim_ascii()
+        .strip_suffix(b">>")
+        .ok_or_else(missing)?
+        .trim_ascii_end()
+        .to_vec();
+
+    // The metadata stream of the document catalog
+    let root = number_after(&trailer, b"/Root").ok_or_else(missing)?;
+    let catalog = object(pdf, xref, root).ok_or_else(missing)?;
+    let id = number_after(catalog, b"/Metadata").ok_or_else(missing)?;
+    let stream = object(pdf, xref, id).ok_or_else(missing)?;
+    let length = number_after(stream, b"/Length").ok_or_else(missing)?;
+    let data = &stream[find(stream, b"stream").ok_or_else(missing)? + b"stream".len()..];
+    let data = data
+        .strip_prefix(b"\r\n")
+        .or_else(|| data.strip_prefix(b"\n"))
+        .and_then(|data| data.get(..length))
+        .ok_or_else(missing)?;
+    let packet = std::str::from_utf8(data).map_err(|_| missing())?;
+    let end = packet.rfind("</rdf:RDF>").ok_or_else(missing)?;
+    let packet = format!("{}{}{}", &packet[..end], xmp, &packet[end..]);
+
+    // Write the stream again under the same object number, so the catalog
+    // refers to the new one
+    pdf.push(b'\n');
+    let offset = pdf.len();
+    write!(
+        pdf,
+        "{} 0 obj\n<<\n  /Length {}\n  /Type /Metadata\n  /Subtype /XML\n>>\nstream\n{}\nendstream\nendobj\n\n",
+        id,
+        packet.len(),
+        packet
+    )
+    .unwrap();
+
+    let update = pdf.len();
+    write!(pdf, "xref\n{} 1\n{:010} 00000 n\r\ntrailer\n", id, offset).unwrap();
+    pdf.extend_from_slice(&trailer);
+    write!(pdf, "\n  /Prev {}\n>>\nstartxref\n{}\n%%EOF", xref, update).unwrap();
+
+    Ok(())
+}
+
+/// The bytes of object `id` up to its `endobj`, located through the
+/// cross-reference section at `xref`
+///
+/// Only sections with a single subsection are read, which is how typst-pdf
+/// writes them.
+fn object(pdf: &[u8], xref: usize, id: usize) -> Option<&[u8]> {
+    let section = pdf.get(xref..)?.strip_prefix(b"xref")?;
+    let (first, section) = number(section.trim_ascii_start())?;
+    let (count, entries) = number(section.trim_ascii_start())?;
+    if id < first || id >= first + count {
+        return None;
+    }
+
+    // Entries are 20 bytes long and start with the 10-digit byte offset
+    let entry = entries.trim_ascii_start().get((id - first) * 20..)?;
+    let (offset, _) = number(entry.get(..10)?)?;
+    let object = pdf.get(offset..)?;
+    Some(&object[..find(object, b"endobj")?])
+}
+
+/// The integer following `key` in `bytes`
+fn number_after(bytes: &[u8], key: &[u8]) -> Option<usize> {
+    let rest = &bytes[find(bytes, key)? + key.len()..];
+    number(rest.trim_ascii_start()).map(|(number, _)| number)
+}
+
+/// The integer at the start of `bytes` and the bytes that follow it
+fn number(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let len = bytes.iter().take_while(|b| b.is_ascii_digit()).count();
+    let number = std::str::from_utf8(&bytes[..len]).ok()?.parse().ok()?;
+    Some((number, &bytes[len..]))
+}
+
+fn find(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(bytes: &[u8], needle: &[u8]) -> Option<usize> {
+    bytes
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Engine, EngineConfig};
+    use crate::world::TypstWorld;
+    use chrono::{DateTime, NaiveDate};
+    use std::sync::Arc;
+    use typst::foundations::{Dict, Smart};
+    use typst_layout::PagedDocument;
+    use typst_pdf::PdfOptions;
+
+    fn pdf(options: &PdfOptions) -> Vec<u8> {
+        let engine = Arc::new(Engine::new(EngineConfig::default()).unwrap());
+        let world =
+            TypstWorld::new(engine, "= Report".to_string(), Dict::new(), Dict::new()).unwrap();
+        let document = typst::compile::<PagedDocument>(&world).output.unwrap();
+        typst_pdf::pdf(&document, options).unwrap()
+    }
+
+    fn render(options: &PdfOptions) -> String {
+        String::from_utf8_lossy(&pdf(options)).into_owned()
+    }
+
+    #[test]
+    fn parses_standard_names() {
+        assert_eq!(parse_standard("a-3b").ok(), Some(PdfStandard::A_3b));
+        assert_eq!(parse_standard("UA_1").ok(), Some(PdfStandard::Ua_1));
+        assert_eq!(parse_standard("2.0").ok(), Some(PdfStandard::V_2_0));
+
+        match parse_standard("a-9z") {
+            Err(NifError::Message(message)) => {
+                assert!(message.starts_with("Unknown PDF standard 'a-9z'"))
+            }
+            _ => panic!("expected an error message"),
+        }
+    }
+
+    #[test]
+    fn reports_incompatible_standards_as_diagnostics() {
+        let names = ["a-1b".to_string(), "2.0".to_string()];
+
+        match standards(&names) {
+            Err(NifError::Diagnostics(diagnostics)) => {
+                assert_eq!(diagnostics.len(), 1);
+                assert_eq!(
+                    diagnostics[0].message,
+                    "PDF 2.0 is not compatible with PDF/A-1b"
+                );
+                assert_eq!(diagnostics[0].hints, ["PDF/A-1b requires version PDF 1.4"]);
+            }
+            _ => panic!("expected diagnostics"),
+        }
+    }
+
+    #[test]
+    fn writes_the_creation_timestamp() {
+        let time = DateTime::parse_from_rfc3339("2024-02-29T23:30:00+02:00").unwrap();
+        let options = PdfOptions {
+            timestamp: Some(timestamp(DateValue::DateTime(time)).unwrap()),
+            ..PdfOptions::default()
+        };
+        assert!(render(&options).contains("/CreationDate(D:20240229233000+02'00)"));
+
+        let date = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap();
+        let options = PdfOptions {
+            timestamp: Some(timestamp(DateValue::Date(date)).unwrap()),
+            ..PdfOptions::default()
+        };
+        assert!(render(&options).contains("/CreationDate(D:20240229"));
+
+        assert!(!render(&PdfOptions::default()).contains("/CreationDate"));
+    }
+
+    #[test]
+    fn renders_identical_documents_identically() {
+        assert_eq!(
+            render(&PdfOptions::default()),
+            render(&PdfOptions::default())
+        );
+
+        let options = PdfOptions {
+            ident: Smart::Custom("invoice-1".to_string()),
+            ..PdfOptions::default()
+        };
+        assert_ne!(render(&options), render(&PdfOptions::default()));
+        assert_eq!(render(&options), render(&options));
+    }
+
+    #[test]
+    fn adds_custom_xmp_in_an_incremental_update() {
+        let xmp = r#"<rdf:Description rdf:about="" xmlns:fx="urn:factur-x"><fx:DocumentType>INVOICE</fx:DocumentType></rdf:Description>"#;
+        let original = pdf(&PdfOptions::default());
+        let mut updated = original.clone();
+        add_xmp(&mut updated, xmp).unwrap();
+        assert!(updated.starts_with(&original));
+
+        // The last cross-reference section points to the new packet, and to
+        // the section typst-pdf wrote
+        let startxref = rfind(&updated, b"startxref").unwrap();
+        let xref = number_after(&updated[startxref..], b"startxref").unwrap();
+        let id = number_after(&updated[xref..], b"xref").unwrap();
+        let stream = String::from_utf8_lossy(object(&updated, xref, id).unwrap());
+        assert!(stream.starts_with(&format!("{} 0 obj", id)));
+        assert!(stream.contains(&format!("{}</rdf:RDF></x:xmpmeta>", xmp)));
+        assert!(stream.contains("<xmp:CreatorTool>"));
+
+        let original_xref = number_after(
+            &original[rfind(&original, b"startxref").unwrap()..],
+            b"startxref",
+        );
+        let trailer = String::from_utf8_lossy(&updated[xref..]);
+        assert!(trailer.contains(&format!("/Prev {}", original_xref.unwrap())));
+        assert!(trailer.ends_with("%%EOF"));
+    }
+}
@@ -0,0 +1,291 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustler::NifUnitEnum;
+use typst::syntax::{FileId, VirtualRoot};
+
+use crate::TypstError;
+
+/// How far templates may reach into the file system, encoded as `:disabled`,
+/// `:strict` or `:memory`
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SandboxMode {
+    /// Project files are read from anywhere the root path leads, including
+    /// through symlinks
+    #[default]
+    Disabled,
+    /// Project files must be inside the root after resolving symlinks
+    Strict,
+    /// Project files are only served from memory, never from disk
+    Memory,
+}
+
+/// Why a file read was refused, encoded as `:outside_root`,
+/// `:disk_access_disabled`, `:extension_not_allowed`, `:file_too_large` or
+/// `:total_size_exceeded`
+#[derive(NifUnitEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenialReason {
+    OutsideRoot,
+    DiskAccessDisabled,
+    ExtensionNotAllowed,
+    FileTooLarge,
+    TotalSizeExceeded,
+}
+
+/// Limits on the files a template may load, whether from memory, the project
+/// root or a package
+#[derive(Debug, Clone, Default)]
+pub struct FileLimits {
+    /// Extensions that may be loaded, without the leading dot; `None` allows
+    /// every extension
+    pub allowed_extensions: Option<Vec<String>>,
+    /// Maximum size in bytes of a single file
+    pub max_file_size: Option<u64>,
+    /// Maximum size in bytes of all files loaded by one render
+    pub max_total_size: Option<u64>,
+}
+
+/// A file read refused by the sandbox
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessDenial {
+    /// The path as requested by the template, relative to the project root
+    pub path: String,
+    pub reason: DenialReason,
+}
+
+impl fmt::Display for AccessDenial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            DenialReason::OutsideRoot => {
+                write!(
+                    f,
+                    "access denied: {} is outside the project root",
+                    self.path
+                )
+            }
+            DenialReason::DiskAccessDisabled => write!(
+                f,
+                "access denied: {} is not in memory and disk access is disabled",
+                self.path
+            ),
+            DenialReason::ExtensionNotAllowed => write!(
+                f,
+                "access denied: {} does not have an allowed file extension",
+                self.path
+            ),
+            DenialReason::FileTooLarge => write!(
+                f,
+                "access denied: {} exceeds the maximum file size",
+                self.path
+            ),
+            DenialReason::TotalSizeExceeded => write!(
+                f,
+                "access denied: reading {} exceeds the maximum total size of files",
+                self.path
+            ),
+        }
+    }
+}
+
+/// Restrictions on the files a template may load
+///
+/// The sandbox mode only restricts project files; package files are resolved
+/// from the package paths and the package cache, which are under the
+/// application's control. The file limits apply to every file.
+#[derive(Debug, Clone, Default)]
+pub struct Sandbox {
+    mode: SandboxMode,
+    /// The project root with symlinks resolved, in strict mode
+    root: Option<PathBuf>,
+    limits: FileLimits,
+}
+
+impl Sandbox {
+    /// Create a sandbox for the project files under `root`
+    pub fn new(mode: SandboxMode, root: &Path, limits: FileLimits) -> Result<Self, TypstError> {
+        let root = match mode {
+            SandboxMode::Strict => Some(fs::canonicalize(root).map_err(|e| {
+                TypstError::InvalidInput(format!(
+                    "Root path '{}' can't be sandboxed: {}",
+                    root.display(),
+                    e
+                ))
+            })?),
+            SandboxMode::Disabled | SandboxMode::Memory => None,
+        };
+
+        let limits = FileLimits {
+            allowed_extensions: limits.allowed_extensions.map(|extensions| {
+                extensions
+                    .iter()
+                    .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                    .collect()
+            }),
+            ..limits
+        };
+
+        Ok(Self { mode, root, limits })
+    }
+
+    /// Check that the file `id` may be loaded at all, before looking it up
+    ///
+    /// Package files are exempt, as packages load their own manifests and
+    /// assets whatever the template may load.
+    pub fn check_extension(&self, id: FileId) -> Result<(), AccessDenial> {
+        let Some(allowed) = &self.limits.allowed_extensions else {
+            return Ok(());
+        };
+
+        if let VirtualRoot::Package(_) = id.root() {
+            return Ok(());
+        }
+
+        match id.vpath().extension().map(str::to_lowercase) {
+            Some(extension) if allowed.contains(&extension) => Ok(()),
+            _ => Err(deny(id, DenialReason::ExtensionNotAllowed)),
+        }
+    }
+
+    /// Check the size of the file `id` and the total size of the files
+    /// loaded so far, including this one
+    pub fn check_size(&self, id: FileId, size: u64, total: u64) -> Result<(), AccessDenial> {
+        if self.limits.max_file_size.is_some_and(|max| size > max) {
+            return Err(deny(id, DenialReason::FileTooLarge));
+        }
+
+        if self.limits.max_total_size.is_some_and(|max| total > max) {
+            return Err(deny(id, DenialReason::TotalSizeExceeded));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the file `id`, resolved to `path`, may be read from disk
+    ///
+    /// Returns the path the file should be read from. In strict mode this is
+    /// the path with symlinks resolved, so the checked file is the one read.
+    pub fn check(&self, id: FileId, path: &Path) -> Result<PathBuf, AccessDenial> {
+        let path = self.check_location(id, path)?;
+
+        // Refuse oversized files before reading them into memory
+        if let Some(max) = self.limits.max_file_size {
+            if fs::metadata(&path).is_ok_and(|metadata| metadata.len() > max) {
+                return Err(deny(id, DenialReason::FileTooLarge));
+            }
+        }
+
+        Ok(path)
+    }
+
+    fn check_location(&self, id: FileId, path: &Path) -> Result<PathBuf, AccessDenial> {
+        if let VirtualRoot::Package(_) = id.root() {
+            return Ok(path.to_path_buf());
+        }
+
+        let deny = |reason| deny(id, reason);
+
+        match (self.mode, &self.root) {
+            (SandboxMode::Memory, _) => Err(deny(DenialReason::DiskAccessDisabled)),
+            (SandboxMode::Strict, Some(root)) => match fs::canonicalize(path) {
+                Ok(real) if real.starts_with(root) => Ok(real),
+                Ok(_) => Err(deny(DenialReason::OutsideRoot)),
+                // Missing files are reported when they are read
+                Err(_) => Ok(path.to_path_buf()),
+            },
+            _ => Ok(path.to_path_buf()),
+        }
+    }
+}
+
+fn deny(id: FileId, reason: DenialReason) -> AccessDenial {
+    AccessDenial {
+        path: id.vpath().get_with_slash().to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use typst::syntax::{RootedPath, VirtualPath};
+
+    fn project_file(path: &str) -> FileId {
+        FileId::new(RootedPath::new(
+            VirtualRoot::Project,
+            VirtualPath::new(path).unwrap(),
+        ))
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn strict_mode_rejects_symlinks_escaping_the_root() {
+        let base = std::env::temp_dir().join(format!("typster-sandbox-{}", std::process::id()));
+        let root = base.join("root");
+        fs::create_dir_all(root.join("assets")).unwrap();
+        fs::write(base.join("secret.txt"), "secret").unwrap();
+        fs::write(root.join("assets/data.txt"), "data").unwrap();
+        std::os::unix::fs::symlink(base.join("secret.txt"), root.join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(root.join("assets"), root.join("inside")).unwrap();
+
+        let sandbox = Sandbox::new(SandboxMode::Strict, &root, FileLimits::default()).unwrap();
+
+        let denial = sandbox
+            .check(project_file("link.txt"), &root.join("link.txt"))
+            .unwrap_err();
+        assert_eq!(denial.path, "/link.txt");
+        assert_eq!(denial.reason, DenialReason::OutsideRoot);
+
+        let id = project_file("inside/data.txt");
+        assert!(sandbox.check(id, &root.join("inside/data.txt")).is_ok());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn memory_mode_denies_project_files() {
+        let sandbox =
+            Sandbox::new(SandboxMode::Memory, Path::new("."), FileLimits::default()).unwrap();
+
+        let denial = sandbox
+            .check(project_file("etc/passwd"), Path::new("./etc/passwd"))
+            .unwrap_err();
+        assert_eq!(denial.reason, DenialReason::DiskAccessDisabled);
+        assert_eq!(
+            denial.to_string(),
+            "access denied: /etc/passwd is not in memory and disk access is disabled"
+        );
+    }
+
+    #[test]
+    fn limits_file_extensions_and_sizes() {
+        let limits = FileLimits {
+            allowed_extensions: Some(vec![".PNG".to_string(), "typ".to_string()]),
+            max_file_size: Some(100),
+            max_total_size: Some(150),
+        };
+        let sandbox = Sandbox::new(SandboxMode::Disabled, Path::new("."), limits).unwrap();
+
+        assert!(sandbox.check_extension(project_file("logo.png")).is_ok());
+        assert!(sandbox
+            .check_extension(project_file("lib/header.typ"))
+            .is_ok());
+        for path in ["data.json", "Makefile"] {
+            let denial = sandbox.check_extension(project_file(path)).unwrap_err();
+            assert_eq!(denial.reason, DenialReason::ExtensionNotAllowed);
+        }
+
+        let manifest = FileId::new(RootedPath::new(
+            VirtualRoot::Package("@preview/tiaoma:0.3.0".parse().unwrap()),
+            VirtualPath::new("typst.toml").unwrap(),
+        ));
+        assert!(sandbox.check_extension(manifest).is_ok());
+
+        let id = project_file("logo.png");
+        assert!(sandbox.check_size(id, 100, 150).is_ok());
+        let denial = sandbox.check_size(id, 101, 101).unwrap_err();
+        assert_eq!(denial.reason, DenialReason::FileTooLarge);
+        let denial = sandbox.check_size(id, 60, 160).unwrap_err();
+        assert_eq!(denial.reason, DenialReason::TotalSizeExceeded);
+    }
+}
@@ -1,234 +1,259 @@
 use std::collections::HashMap;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
-use chrono::Datelike;
-use typst::diag::FileResult;
-use typst::foundations::{Bytes, Datetime, Dict, Duration, Value};
-use typst::syntax::{FileId, RootedPath, Source, VirtualPath, VirtualRoot};
+use chrono::{Datelike, NaiveDate};
+use typst::diag::{FileError, FileResult};
+use typst::foundations::{Binding, Bytes, Datetime, Dict, Duration, Module, Scope, Styles};
+use typst::syntax::{is_ident, FileId, RootedPath, Source, VirtualPath, VirtualRoot};
 use typst::text::{Font, FontBook};
 use typst::utils::LazyHash;
 use typst::{Library, LibraryExt, World};
-use typst_kit::fonts::{self, FontStore};
 
-use crate::packages;
+use crate::convert::DateValue;
+use crate::engine::Engine;
+use crate::sandbox::AccessDenial;
 use crate::TypstError;
 
+/// Name of the global module holding the variables passed from Elixir
+///
+/// The main file wildcard-imports it, so the variables are visible as
+/// top-level names in the template but not inside imported files or packages.
+const VARIABLES_MODULE: &str = "__typster_variables";
+
 /// A simple implementation of the World trait for Typst compilation
 pub struct TypstWorld {
-    /// The root directory for resolving files
-    root: PathBuf,
-    /// Additional package directories to search
-    package_paths: Vec<PathBuf>,
-    /// Directory for caching downloaded packages
-    package_cache_dir: PathBuf,
-    /// The standard library
+    /// Fonts, library and file access shared with other renders
+    engine: Arc<Engine>,
+    /// The standard library with the variables and inputs bound
     library: LazyHash<Library>,
-    /// Font store providing the font book and lazily-loaded fonts
-    fonts: FontStore,
-    /// Cache of loaded source files
+    /// Sources that don't come from a file, i.e. the main source
     sources: HashMap<FileId, Source>,
+    /// Files passed in memory, served instead of files on disk
+    files: HashMap<FileId, Bytes>,
+    /// Files the sandbox refused to read, in the order they were requested
+    denials: Mutex<Vec<AccessDenial>>,
+    /// Files and sources loaded by this render, so each is only read once
+    slots: Mutex<HashMap<FileId, FileSlot>>,
+    /// Sizes of the files loaded so far, counted towards the total size limit
+    loaded: Mutex<HashMap<FileId, u64>>,
     /// Main source file
     main: FileId,
-    /// Cache of loaded files (non-source)
-    files: HashMap<FileId, Bytes>,
-    /// Mutex to ensure thread-safe package downloads
-    download_lock: Mutex<()>,
+    /// Length in bytes of the variable import prepended to the main source
+    prefix_len: usize,
+    /// Date reported instead of the current date, for reproducible output
+    ///
+    /// A date is the same for every offset, while the date of a point in
+    /// time depends on the offset, and on its own UTC offset when no offset
+    /// is given.
+    today: Option<DateValue>,
 }
 
 impl TypstWorld {
-    /// Create a new TypstWorld with the given source code, variables, and package paths
+    /// Create a new TypstWorld with the given engine, source code and variables
+    ///
+    /// `variables` become top-level names in the template, while `inputs` are
+    /// exposed through `sys.inputs` like the Typst CLI's `--input` flag.
     pub fn new(
+        engine: Arc<Engine>,
         source_code: String,
         variables: Dict,
-        package_paths: Vec<PathBuf>,
-        root_path: PathBuf,
+        inputs: Dict,
     ) -> Result<Self, TypstError> {
         // Create a virtual path for the main source
         let main_path = VirtualPath::new("main.typ")
             .map_err(|e| TypstError::InvalidInput(format!("Invalid main path: {}", e)))?;
         let main_id = FileId::new(RootedPath::new(VirtualRoot::Project, main_path));
 
-        // Generate variable declarations
-        let mut var_declarations = String::new();
-        for (key, value) in variables.iter() {
-            let typst_value = Self::value_to_typst_repr(value);
-            var_declarations.push_str(&format!("#let {} = {}\n", key.as_str(), typst_value));
-        }
-
-        // Prepend variable declarations to the source code
-        let full_source = if var_declarations.is_empty() {
-            source_code
+        // Import the variables into the main file, unless there are none
+        let var_import = if variables.is_empty() {
+            String::new()
         } else {
-            format!("{}\n{}", var_declarations, source_code)
+            format!("#import {}: *\n", VARIABLES_MODULE)
         };
 
+        let library = Self::build_library(&engine, variables, inputs)?;
+
+        // Prepend the variable import to the source code, remembering where
+        // the user's code starts so diagnostics can be remapped
+        let prefix_len = var_import.len();
+        let full_source = format!("{}{}", var_import, source_code);
+
         // Parse the source
         let source = Source::new(main_id, full_source);
 
-        // Initialize fonts
-        let fonts = Self::search_fonts();
-
         let mut sources = HashMap::new();
         sources.insert(main_id, source);
 
-        // Get or create the package cache directory
-        let package_cache_dir = packages::get_cache_dir()?;
-
         Ok(Self {
-            root: root_path,
-            package_paths,
-            package_cache_dir,
-            library: LazyHash::new(Library::default()),
-            fonts,
+            engine,
+            library: LazyHash::new(library),
             sources,
-            main: main_id,
             files: HashMap::new(),
-            download_lock: Mutex::new(()),
+            denials: Mutex::new(Vec::new()),
+            slots: Mutex::new(HashMap::new()),
+            loaded: Mutex::new(HashMap::new()),
+            main: main_id,
+            prefix_len,
+            today: None,
         })
     }
 
-    /// Convert a Typst Value to its Typst code representation
-    fn value_to_typst_repr(value: &Value) -> String {
-        match value {
-            Value::None => "none".to_string(),
-            Value::Auto => "auto".to_string(),
-            Value::Bool(b) => b.to_string(),
-            Value::Int(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
-            Value::Str(s) => format!("\"{}\"", s.as_str().replace('\\', "\\\\").replace('"', "\\\"")),
-            Value::Array(arr) => {
-                let items: Vec<String> = arr.iter().map(|v| Self::value_to_typst_repr(v)).collect();
-                // In Typst, a single-element array needs a trailing comma: (item,)
-                // Without it, (item) is just a parenthesized expression, not an array
-                if items.len() == 1 {
-                    format!("({},)", items[0])
-                } else {
-                    format!("({})", items.join(", "))
-                }
-            }
-            Value::Dict(dict) => {
-                let items: Vec<String> = dict
-                    .iter()
-                    .map(|(k, v)| {
-                        // Always quote dictionary keys to handle:
-                        // - Keys with special characters (e.g., "2025-01" would be parsed as subtraction)
-                        // - Keys that conflict with Typst built-ins (e.g., "h" for horizontal spacing)
-                        format!(
-                            "\"{}\": {}",
-                            k.as_str().replace('\\', "\\\\").replace('"', "\\\""),
-                            Self::value_to_typst_repr(v)
-                        )
-                    })
-                    .collect();
-                format!("({})", items.join(", "))
-            }
-            Value::Datetime(dt) => {
-                // Convert Datetime to Typst datetime constructor call
-                match (
-                    dt.year(),
-                    dt.month(),
-                    dt.day(),
-                    dt.hour(),
-                    dt.minute(),
-                    dt.second(),
-                ) {
-                    (Some(y), Some(m), Some(d), Some(h), Some(min), Some(s)) => {
-                        // Full datetime
-                        format!("datetime(year: {}, month: {}, day: {}, hour: {}, minute: {}, second: {})", y, m, d, h, min, s)
-                    }
-                    (Some(y), Some(m), Some(d), None, None, None) => {
-                        // Date only
-                        format!("datetime(year: {}, month: {}, day: {})", y, m, d)
-                    }
-                    (None, None, None, Some(h), Some(min), Some(s)) => {
-                        // Time only
-                        format!("datetime(hour: {}, minute: {}, second: {})", h, min, s)
-                    }
-                    _ => "none".to_string(), // Invalid datetime
-                }
-            }
-            _ => "none".to_string(), // For unsupported types, use none
+    /// Serve `bytes` as the file at `path`, relative to the project root,
+    /// instead of reading it from disk
+    pub fn add_file(&mut self, path: &str, bytes: Bytes) -> Result<(), TypstError> {
+        let vpath = VirtualPath::new(path).map_err(|e| {
+            TypstError::InvalidInput(format!("Invalid file path '{}': {}", path, e))
+        })?;
+        let id = FileId::new(RootedPath::new(VirtualRoot::Project, vpath));
+        if id == self.main {
+            return Err(TypstError::InvalidInput(format!(
+                "File path '{}' is reserved for the template itself",
+                path
+            )));
         }
+
+        self.files.insert(id, bytes);
+        Ok(())
     }
 
-    /// Build a font store containing both system and embedded fonts.
-    fn search_fonts() -> FontStore {
-        let mut store = FontStore::new();
-        // System fonts first, so user-installed fonts take precedence, then the
-        // embedded fallback fonts guarantee a baseline set is always available.
-        store.extend(fonts::system());
-        store.extend(fonts::embedded());
-        store
+    /// Apply style properties before the template's own, as if the template
+    /// started with the corresponding set rules
+    pub fn add_styles(&mut self, mut styles: Styles) {
+        styles.apply(std::mem::take(&mut self.library.styles));
+        self.library.styles = styles;
     }
 
-    /// Resolve a FileId to an actual file system path
-    fn resolve_path(&self, id: FileId) -> FileResult<PathBuf> {
-        // Check if this is a package file
-        if let VirtualRoot::Package(package) = id.root() {
-            // Try to find the package in configured package_paths first
-            for package_root in &self.package_paths {
-                // Package format: @namespace/name/version
-                let package_dir = package_root
-                    .join(package.namespace.as_str())
-                    .join(package.name.as_str())
-                    .join(package.version.to_string());
-
-                if let Ok(resolved) = id.vpath().realize(&package_dir) {
-                    if resolved.exists() {
-                        return Ok(resolved);
-                    }
-                }
-            }
+    /// Report `today` from `datetime.today()` instead of the current date
+    pub fn set_today(&mut self, today: DateValue) {
+        self.today = Some(today);
+    }
 
-            // Not found in package_paths, try the cache directory
-            let cache_package_dir = self
-                .package_cache_dir
-                .join(package.namespace.as_str())
-                .join(package.name.as_str())
-                .join(package.version.to_string());
-
-            if let Ok(resolved) = id.vpath().realize(&cache_package_dir) {
-                if resolved.exists() {
-                    return Ok(resolved);
-                }
-            }
+    /// Load a file or source once per render, reusing the result afterwards
+    ///
+    /// The slot isn't locked while loading, since loading a source loads the
+    /// file as well.
+    fn cached<T: Clone>(
+        &self,
+        id: FileId,
+        slot: fn(&mut FileSlot) -> &mut Option<FileResult<T>>,
+        load: impl FnOnce() -> FileResult<T>,
+    ) -> FileResult<T> {
+        if let Some(result) = slot(self.slots.lock().unwrap().entry(id).or_default()) {
+            return result.clone();
+        }
 
-            // Not in cache either, try to download it
-            // Use a lock to prevent concurrent downloads of the same package
-            let _lock = self.download_lock.lock().unwrap();
+        let result = load();
+        *slot(self.slots.lock().unwrap().entry(id).or_default()) = Some(result.clone());
+        result
+    }
 
-            // Check again after acquiring lock (another thread might have downloaded it)
-            if let Ok(resolved) = id.vpath().realize(&cache_package_dir) {
-                if resolved.exists() {
-                    return Ok(resolved);
-                }
+    /// Load the file `id` from memory or disk, within the sandbox limits
+    fn load(&self, id: FileId) -> FileResult<Bytes> {
+        let sandbox = self.engine.sandbox();
+        sandbox
+            .check_extension(id)
+            .map_err(|denial| self.deny(denial))?;
+
+        // Files passed in memory take precedence over the disk
+        let bytes = match self.files.get(&id) {
+            Some(bytes) => bytes.clone(),
+            None => {
+                let path = self.engine.resolve_path(id)?;
+                let path = sandbox
+                    .check(id, &path)
+                    .map_err(|denial| self.deny(denial))?;
+                self.engine.read(&path)?
             }
+        };
 
-            // Download the package
-            let downloaded_dir = packages::download_package(package, &self.package_cache_dir)
-                .map_err(|e| typst::diag::FileError::Other(Some(e.to_string().into())))?;
+        let size = bytes.len() as u64;
+        let mut loaded = self.loaded.lock().unwrap();
+        let total = loaded.values().sum::<u64>() - loaded.get(&id).unwrap_or(&0) + size;
+        sandbox
+            .check_size(id, size, total)
+            .map_err(|denial| self.deny(denial))?;
+        loaded.insert(id, size);
 
-            // Now try to resolve the path again
-            id.vpath().realize(&downloaded_dir).map_err(|_| {
-                typst::diag::FileError::NotFound(id.vpath().get_without_slash().into())
-            })
-        } else {
-            // Not a package file, resolve relative to root
-            id.vpath().realize(&self.root).map_err(|_| {
-                typst::diag::FileError::NotFound(id.vpath().get_without_slash().into())
-            })
+        Ok(bytes)
+    }
+
+    /// Record a file read refused by the sandbox, returning the error reported
+    /// to the compiler
+    fn deny(&self, denial: AccessDenial) -> FileError {
+        let error = FileError::Other(Some(denial.to_string().into()));
+        self.denials.lock().unwrap().push(denial);
+        error
+    }
+
+    /// The engine the world loads its files through
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// The first file read refused by the sandbox, if any
+    pub fn access_denial(&self) -> Option<AccessDenial> {
+        self.denials.lock().unwrap().first().cloned()
+    }
+
+    /// Length in bytes of the variable import that precedes the user's
+    /// source in the main file
+    ///
+    /// The import always ends with a newline, so the user's first line starts
+    /// right after it.
+    pub fn prefix_len(&self) -> usize {
+        self.prefix_len
+    }
+
+    /// Build the standard library with the variables bound as values
+    ///
+    /// The variables are bound in a module rather than converted to Typst
+    /// code, so large datasets don't have to be serialized and re-parsed.
+    /// The engine's library is reused unless there are inputs to bind.
+    fn build_library(
+        engine: &Engine,
+        variables: Dict,
+        inputs: Dict,
+    ) -> Result<Library, TypstError> {
+        let mut scope = Scope::new();
+        for (key, value) in variables {
+            if !is_ident(key.as_str()) {
+                return Err(TypstError::InvalidInput(format!(
+                    "Variable name '{}' is not a valid Typst identifier",
+                    key.as_str()
+                )));
+            }
+            scope.bind(key.as_str().into(), Binding::detached(value));
         }
+
+        let mut library = if inputs.is_empty() {
+            engine.library().clone()
+        } else {
+            Library::builder().with_inputs(inputs).build()
+        };
+        let module = Module::new(VARIABLES_MODULE, scope);
+        library
+            .global
+            .scope_mut()
+            .bind(VARIABLES_MODULE.into(), Binding::detached(module));
+
+        Ok(library)
     }
 }
 
+/// A file loaded by a render, as bytes and as a parsed source
+#[derive(Default)]
+struct FileSlot {
+    file: Option<FileResult<Bytes>>,
+    source: Option<FileResult<Source>>,
+}
+
 impl World for TypstWorld {
     fn library(&self) -> &LazyHash<Library> {
         &self.library
     }
 
     fn book(&self) -> &LazyHash<FontBook> {
-        self.fonts.book()
+        self.engine.fonts().book()
     }
 
     fn main(&self) -> FileId {
@@ -236,53 +261,239 @@ impl World for TypstWorld {
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
-        // Check cache first
         if let Some(source) = self.sources.get(&id) {
             return Ok(source.clone());
         }
 
-        // Try to load the source file from disk
-        let path = self.resolve_path(id)?;
-        let content = std::fs::read_to_string(&path)
-            .map_err(|e| typst::diag::FileError::from_io(e, &path))?;
-
-        Ok(Source::new(id, content))
+        self.cached(
+            id,
+            |slot| &mut slot.source,
+            || self.engine.source(id, &self.file(id)?),
+        )
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
-        // Check cache first
-        if let Some(bytes) = self.files.get(&id) {
-            return Ok(bytes.clone());
-        }
-
-        // Try to resolve the file path
-        let path = self.resolve_path(id)?;
-
-        // Read the file
-        let bytes = std::fs::read(&path).map_err(|e| typst::diag::FileError::from_io(e, &path))?;
-
-        Ok(Bytes::new(bytes))
+        self.cached(id, |slot| &mut slot.file, || self.load(id))
     }
 
     fn font(&self, index: usize) -> Option<Font> {
-        self.fonts.font(index)
+        self.engine.fonts().font(index)
     }
 
     fn today(&self, offset: Option<Duration>) -> Option<Datetime> {
+        let now = match self.today {
+            Some(DateValue::Date(date)) => return to_datetime(date),
+            Some(DateValue::DateTime(now)) => now,
+            None => chrono::Local::now().fixed_offset(),
+        };
+
         // Per the `World::today` contract: with no offset, use the local date;
         // with an offset, use the UTC date shifted by that offset.
-        let (year, month, day) = match offset {
-            None => {
-                let now = chrono::Local::now();
-                (now.year(), now.month(), now.day())
-            }
+        let date = match offset {
+            None => now.date_naive(),
             Some(offset) => {
-                let now = chrono::Utc::now();
-                let adjusted = now + chrono::Duration::seconds(offset.seconds() as i64);
-                (adjusted.year(), adjusted.month(), adjusted.day())
+                let adjusted = now.naive_utc() + chrono::Duration::seconds(offset.seconds() as i64);
+                adjusted.date()
             }
         };
 
-        Datetime::from_ymd(year, month.try_into().ok()?, day.try_into().ok()?)
+        to_datetime(date)
+    }
+}
+
+fn to_datetime(date: NaiveDate) -> Option<Datetime> {
+    Datetime::from_ymd(
+        date.year(),
+        date.month().try_into().ok()?,
+        date.day().try_into().ok()?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::EngineConfig;
+    use crate::sandbox::{DenialReason, FileLimits};
+    use chrono::DateTime;
+    use typst::foundations::{Array, Value};
+    use typst_layout::PagedDocument;
+
+    fn world(source: &str, variables: Dict) -> Result<TypstWorld, TypstError> {
+        world_with_inputs(source, variables, Dict::new())
+    }
+
+    fn world_with_inputs(
+        source: &str,
+        variables: Dict,
+        inputs: Dict,
+    ) -> Result<TypstWorld, TypstError> {
+        TypstWorld::new(engine(), source.to_string(), variables, inputs)
+    }
+
+    fn engine() -> Arc<Engine> {
+        Arc::new(Engine::new(EngineConfig::default()).unwrap())
+    }
+
+    fn compiles(world: &TypstWorld) -> bool {
+        typst::compile::<PagedDocument>(world).output.is_ok()
+    }
+
+    #[test]
+    fn binds_variables_as_values() {
+        let mut row = Dict::new();
+        row.insert("2025-01".into(), Value::Float(1e300));
+        let mut variables = Dict::new();
+        variables.insert("big".into(), Value::Float(1e300));
+        variables.insert("nan".into(), Value::Float(f64::NAN));
+        variables.insert(
+            "rows".into(),
+            Value::Array(Array::from_iter([Value::Dict(row)])),
+        );
+
+        let source = "#assert.eq(big, 1e300)\n\
+                      #assert(nan != nan)\n\
+                      #assert.eq(rows.first().at(\"2025-01\"), 1e300)";
+
+        assert!(compiles(&world(source, variables).unwrap()));
+    }
+
+    #[test]
+    fn variables_shadow_library_definitions() {
+        let mut variables = Dict::new();
+        variables.insert("text".into(), Value::Int(1));
+
+        assert!(compiles(&world("#assert.eq(text, 1)", variables).unwrap()));
+    }
+
+    #[test]
+    fn rejects_invalid_variable_names() {
+        let mut variables = Dict::new();
+        variables.insert("2025-01".into(), Value::Int(1));
+
+        let error = world("= Test", variables).err().unwrap();
+        assert!(error
+            .to_string()
+            .contains("'2025-01' is not a valid Typst identifier"));
+    }
+
+    #[test]
+    fn serves_files_from_memory() {
+        let source = "#import \"lib/header.typ\": title\n\
+                      #assert.eq(title, \"Report\")\n\
+                      #assert.eq(read(\"/data.csv\"), \"a,b\")";
+        let mut world = world(source, Dict::new()).unwrap();
+        world
+            .add_file(
+                "lib/header.typ",
+                Bytes::new(b"#let title = \"Report\"".to_vec()),
+            )
+            .unwrap();
+        world
+            .add_file("/data.csv", Bytes::new(b"a,b".to_vec()))
+            .unwrap();
+
+        assert!(compiles(&world));
+    }
+
+    #[test]
+    fn loads_each_file_once_per_render() {
+        let root = std::env::temp_dir().join(format!("typster-world-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("lib.typ"), "#let a = 1").unwrap();
+
+        let config = EngineConfig {
+            root: root.clone(),
+            ..EngineConfig::default()
+        };
+        let engine = Arc::new(Engine::new(config).unwrap());
+        let world = TypstWorld::new(engine, String::new(), Dict::new(), Dict::new()).unwrap();
+        let id = FileId::new(RootedPath::new(
+            VirtualRoot::Project,
+            VirtualPath::new("lib.typ").unwrap(),
+        ));
+
+        assert_eq!(world.source(id).unwrap().text(), "#let a = 1");
+        std::fs::remove_file(root.join("lib.typ")).unwrap();
+        assert_eq!(world.source(id).unwrap().text(), "#let a = 1");
+        assert_eq!(world.file(id).unwrap().as_slice(), b"#let a = 1");
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn limits_the_total_size_of_loaded_files() {
+        let config = EngineConfig {
+            limits: FileLimits {
+                max_total_size: Some(5),
+                ..FileLimits::default()
+            },
+            ..EngineConfig::default()
+        };
+        let engine = Arc::new(Engine::new(config).unwrap());
+        let source = "#read(\"a.txt\")\n#read(\"a.txt\")\n#read(\"b.txt\")";
+        let mut world =
+            TypstWorld::new(engine, source.to_string(), Dict::new(), Dict::new()).unwrap();
+        world
+            .add_file("a.txt", Bytes::new(b"abc".to_vec()))
+            .unwrap();
+        world
+            .add_file("b.txt", Bytes::new(b"def".to_vec()))
+            .unwrap();
+
+        assert!(!compiles(&world));
+        let denial = world.access_denial().unwrap();
+        assert_eq!(denial.path, "/b.txt");
+        assert_eq!(denial.reason, DenialReason::TotalSizeExceeded);
+    }
+
+    #[test]
+    fn rejects_invalid_file_paths() {
+        let mut world = world("= Test", Dict::new()).unwrap();
+
+        let error = world.add_file("../secret.typ", Bytes::new(Vec::new()));
+        assert!(error
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("escapes project root"));
+
+        let error = world.add_file("main.typ", Bytes::new(Vec::new()));
+        assert!(error
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("reserved for the template"));
+    }
+
+    #[test]
+    fn exposes_inputs_through_sys_inputs() {
+        let mut inputs = Dict::new();
+        inputs.insert("name".into(), Value::Str("Acme".into()));
+        inputs.insert("2025-01".into(), Value::Int(42));
+
+        let source = "#assert.eq(sys.inputs.name, \"Acme\")\n\
+                      #assert.eq(sys.inputs.at(\"2025-01\"), 42)";
+
+        assert!(compiles(
+            &world_with_inputs(source, Dict::new(), inputs).unwrap()
+        ));
+    }
+
+    #[test]
+    fn reports_the_overridden_date() {
+        let hours = |hours| Some(Duration::construct(0, 0, hours, 0, 0));
+        let mut world = world("= Test", Dict::new()).unwrap();
+
+        world.set_today(DateValue::Date(
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap(),
+        ));
+        assert_eq!(world.today(None), Datetime::from_ymd(2024, 2, 29));
+        assert_eq!(world.today(hours(12)), Datetime::from_ymd(2024, 2, 29));
+
+        let now = DateTime::parse_from_rfc3339("2024-03-01T02:00:00+03:00").unwrap();
+        world.set_today(DateValue::DateTime(now));
+        assert_eq!(world.today(None), Datetime::from_ymd(2024, 3, 1));
+        assert_eq!(world.today(hours(0)), Datetime::from_ymd(2024, 2, 29));
+        assert_eq!(world.today(hours(1)), Datetime::from_ymd(2024, 3, 1));
     }
 }
@@ -250,7 +250,7 @@ defmodule Typster.ConcurrentTest do
 
       # All errors should have messages
       Enum.each(results, fn
-        {:error, reason} -> assert is_binary(reason)
+        {:error, reason} -> assert Exception.message(reason) =~ "Compilation failed"
         {:ok, _} -> :ok
       end)
     end
@@ -312,4 +312,33 @@ defmodule Typster.DatetimeTest do
       assert is_binary(pdf)
     end
   end
+
+  describe "metadata date" do
+    test "accepts ISO 8601 dates and datetimes" do
+      assert {:ok, pdf} = Typster.render_pdf("= Report", metadata: %{date: "2024-02-29"})
+      assert pdf =~ "/CreationDate(D:20240229"
+
+      assert {:ok, pdf} =
+               Typster.render_pdf("= Report", metadata: %{date: "2024-03-01T02:30:00+03:00"})
+
+      assert pdf =~ "/CreationDate(D:20240229233000Z)"
+    end
+
+    test "accepts Date, DateTime and NaiveDateTime values" do
+      {:ok, datetime, _} = DateTime.from_iso8601("2024-03-01T02:30:00+03:00")
+
+      for date <- [datetime, ~N[2024-02-29 23:30:00]] do
+        assert {:ok, pdf} = Typster.render_pdf("= Report", metadata: %{date: date})
+        assert pdf =~ "/CreationDate(D:20240229233000Z)"
+      end
+
+      assert {:ok, pdf} = Typster.render_pdf("= Report", metadata: %{date: ~D[2024-02-29]})
+      assert pdf =~ "/CreationDate(D:20240229"
+    end
+
+    test "returns an error for invalid dates" do
+      assert {:error, reason} = Typster.render_pdf("= Report", metadata: %{date: "yesterday"})
+      assert reason =~ "Invalid metadata date 'yesterday'"
+    end
+  end
 end
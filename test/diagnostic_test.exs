@@ -0,0 +1,36 @@
+defmodule Typster.DiagnosticTest do
+  use ExUnit.Case, async: true
+
+  doctest Typster.Diagnostic
+
+  alias Typster.Diagnostic
+
+  describe "format/1" do
+    test "includes path, line and column when known" do
+      diagnostic = %Diagnostic{message: "oops", path: "lib/header.typ", line: 4, column: 2}
+      assert Diagnostic.format(diagnostic) == "lib/header.typ:4:2: error: oops"
+    end
+
+    test "omits the location when the diagnostic is detached" do
+      assert Diagnostic.format(%Diagnostic{severity: :warning, message: "oops"}) ==
+               "warning: oops"
+    end
+
+    test "implements String.Chars" do
+      assert "#{%Diagnostic{message: "oops", path: "main.typ", line: 1}}" ==
+               "main.typ:1: error: oops"
+    end
+  end
+
+  describe "Typster.CompileError" do
+    test "builds its message from diagnostics" do
+      error =
+        Typster.CompileError.exception(
+          diagnostics: [%Diagnostic{message: "oops", path: "main.typ", line: 1, column: 1}]
+        )
+
+      assert error.message == "Compilation failed:\nmain.typ:1:1: error: oops"
+      assert "#{error}" == error.message
+    end
+  end
+end
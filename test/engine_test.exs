@@ -0,0 +1,128 @@
+defmodule Typster.EngineTest do
+  use ExUnit.Case, async: true
+
+  @template """
+  #set page(width: 200pt, height: 100pt)
+  = Hello #name
+  """
+
+  setup do
+    {:ok, engine} = Typster.Engine.new()
+    %{engine: engine}
+  end
+
+  test "new/1 returns an engine handle", %{engine: engine} do
+    assert is_reference(engine)
+  end
+
+  test "renders every format with an engine", %{engine: engine} do
+    opts = [engine: engine, variables: %{name: "World"}]
+
+    assert {:ok, "%PDF" <> _} = Typster.render_pdf(@template, opts)
+    assert {:ok, [svg]} = Typster.render_svg(@template, opts)
+    assert svg =~ "<svg"
+    assert {:ok, [png]} = Typster.render_png(@template, opts)
+    assert <<137, 80, 78, 71, _::binary>> = png
+    assert :ok = Typster.check(@template, opts)
+  end
+
+  test "reports diagnostics when rendering with an engine", %{engine: engine} do
+    assert {:error, %Typster.CompileError{diagnostics: [diagnostic]}} =
+             Typster.render_pdf("#unknown_var", engine: engine)
+
+    assert diagnostic.message == "unknown variable: unknown_var"
+  end
+
+  test "can be reused by concurrent renders", %{engine: engine} do
+    results =
+      1..20
+      |> Task.async_stream(fn i ->
+        Typster.render_pdf(@template, engine: engine, variables: %{name: "#{i}"})
+      end)
+      |> Enum.map(fn {:ok, result} -> result end)
+
+    assert Enum.all?(results, &match?({:ok, "%PDF" <> _}, &1))
+  end
+
+  test "refuses engine options passed along with an engine", %{engine: engine} do
+    assert {:error, reason} =
+             Typster.render_pdf(@template, engine: engine, sandbox: :memory, root_path: "/")
+
+    assert reason == ":root_path, :sandbox can't be combined with :engine, " <>
+             "pass them to Typster.Engine.new/1 instead"
+
+    assert {:error, [%Typster.Diagnostic{message: message}]} =
+             Typster.check(@template, engine: engine, offline: true)
+
+    assert message =~ ":offline can't be combined with :engine"
+
+    assert {:error, _reason} = Typster.list_fonts(engine: engine, fonts: [])
+  end
+
+  describe "package_namespaces" do
+    test "accepts http sources with and without headers" do
+      url = "https://packages.example.com/acme"
+      headers = %{"authorization" => "Bearer token", accept: "application/gzip"}
+
+      assert {:ok, _engine} = Typster.Engine.new(package_namespaces: %{"acme" => {:http, url}})
+
+      namespaces = %{"@acme" => {:http, url, headers: headers}, tools: {:http, url}}
+      assert {:ok, _engine} = Typster.Engine.new(package_namespaces: namespaces)
+
+      assert %{
+               "acme" => {:http, %{url: ^url, headers: %{"authorization" => "Bearer token"}}},
+               "tools" => {:http, %{url: ^url, headers: %{}}}
+             } = Typster.Engine.native_options(package_namespaces: namespaces).package_namespaces
+    end
+
+    test "returns an error for malformed sources" do
+      url = "https://packages.example.com/acme"
+      headers = [{"authorization", "Bearer token"}]
+
+      assert {:error, reason} =
+               Typster.Engine.new(package_namespaces: %{acme: {:http, url, headers: headers}})
+
+      assert reason =~ ~s(Invalid package source {:http, "#{url}", [headers: [)
+      assert reason =~ "for namespace :acme"
+
+      assert {:error, _reason} = Typster.Engine.new(package_namespaces: %{acme: {:ftp, url}})
+      assert {:error, _reason} = Typster.Engine.new(package_namespaces: [acme: {:http, url}])
+
+      assert {:error, _reason} =
+               Typster.render_pdf(@template, package_namespaces: %{acme: {:http, 42}})
+    end
+  end
+
+  describe "files" do
+    @describetag :tmp_dir
+
+    test "resolves imports against the engine's root path", %{tmp_dir: tmp_dir} do
+      File.write!(Path.join(tmp_dir, "greeting.typ"), "#let greeting = \"Hello\"")
+      {:ok, engine} = Typster.Engine.new(root_path: tmp_dir)
+
+      template = """
+      #import "greeting.typ": greeting
+      #assert.eq(greeting, "Hello")
+      """
+
+      assert {:ok, _pdf} = Typster.render_pdf(template, engine: engine)
+    end
+
+    test "picks up files changed on disk", %{tmp_dir: tmp_dir} do
+      path = Path.join(tmp_dir, "value.typ")
+      File.write!(path, "#let value = 1")
+      {:ok, engine} = Typster.Engine.new(root_path: tmp_dir)
+
+      template = """
+      #import "value.typ": value
+      #assert.eq(value, 1)
+      """
+
+      assert :ok = Typster.check(template, engine: engine)
+
+      File.write!(path, "#let value = 22")
+
+      assert :ok = Typster.check(String.replace(template, "1)", "22)"), engine: engine)
+    end
+  end
+end
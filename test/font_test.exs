@@ -0,0 +1,41 @@
+defmodule Typster.FontTest do
+  use ExUnit.Case, async: true
+
+  doctest Typster.Font
+
+  describe "Typster.list_fonts/1" do
+    test "lists the embedded fonts" do
+      assert {:ok, fonts} = Typster.list_fonts(ignore_system_fonts: true)
+      assert Enum.all?(fonts, &(&1.source == :embedded))
+
+      font = Enum.find(fonts, &(&1.family == "Libertinus Serif" and &1.style == :normal))
+      assert %Typster.Font{weight: 400, stretch: 1.0, path: nil, index: nil} = font
+      assert font.coverage.code_points > 0
+      assert Typster.Font.covers?(font, ?A)
+
+      mono = Enum.find(fonts, &(&1.family == "DejaVu Sans Mono"))
+      assert :monospace in mono.flags
+    end
+
+    test "lists the fonts of an engine" do
+      {:ok, engine} = Typster.Engine.new(ignore_system_fonts: true)
+
+      assert Typster.list_fonts(engine: engine) == Typster.list_fonts(ignore_system_fonts: true)
+    end
+
+    test "includes system fonts by default" do
+      assert {:ok, fonts} = Typster.list_fonts()
+      assert Enum.any?(fonts, &(&1.source == :embedded))
+
+      for font <- fonts, font.source == :system do
+        assert is_binary(font.path)
+        assert is_integer(font.index)
+      end
+    end
+
+    test "returns an error for invalid font options" do
+      assert {:error, reason} = Typster.list_fonts(fonts: ["not a font"])
+      assert reason =~ "Font at index 0 is not a valid TTF, OTF or TTC font"
+    end
+  end
+end
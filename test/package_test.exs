@@ -60,10 +60,10 @@ defmodule Typster.PackageTest do
       = Test
       """
 
-      assert {:error, reason} = Typster.render_pdf(template)
-      assert is_binary(reason)
+      assert {:error, %Typster.CompileError{} = reason} = Typster.render_pdf(template)
+      assert [%Typster.Diagnostic{severity: :error} | _] = reason.diagnostics
       # Should contain some indication of failure
-      assert reason =~ ~r/(failed|not found|error)/i
+      assert reason.message =~ ~r/(failed|not found|error)/i
     end
 
     test "returns error for non-existent package version" do
@@ -73,8 +73,7 @@ defmodule Typster.PackageTest do
       = Test
       """
 
-      assert {:error, reason} = Typster.render_pdf(template)
-      assert is_binary(reason)
+      assert {:error, %Typster.CompileError{}} = Typster.render_pdf(template)
     end
 
     test "handles multiple package imports in one document" do
@@ -123,4 +122,257 @@ defmodule Typster.PackageTest do
       assert time < 500_000
     end
   end
+
+  describe "offline mode" do
+    setup :local_package
+
+    test "returns a diagnostic for packages that would have to be downloaded" do
+      template = ~s(#import "@preview/nonexistent-package-12345:1.0.0": foo)
+
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic | _]}} =
+               Typster.render_pdf(template, offline: true)
+
+      assert diagnostic.message =~
+               "package @preview/nonexistent-package-12345:1.0.0 not available offline"
+    end
+
+    test "uses packages from package_paths", %{package_paths: package_paths} do
+      template = ~s(#import "@local/greeting:1.0.0": greet\n#greet("Typster"\))
+
+      assert {:ok, pdf} =
+               Typster.render_pdf(template, package_paths: package_paths, offline: true)
+
+      assert String.starts_with?(pdf, "%PDF")
+
+      {:ok, engine} = Typster.Engine.new(package_paths: package_paths, offline: true)
+      assert {:ok, _pdf} = Typster.render_pdf(template, engine: engine)
+    end
+  end
+
+  describe "allowed_extensions" do
+    setup :local_package
+
+    test "doesn't apply to package files", %{package_paths: package_paths} do
+      template = ~s(#import "@local/greeting:1.0.0": greet\n#greet("Typster"\))
+      opts = [package_paths: package_paths, offline: true, allowed_extensions: ["png"]]
+
+      assert {:ok, _pdf} = Typster.render_pdf(template, opts)
+
+      files = %{"data.json" => "{}"}
+
+      assert {:error, %Typster.AccessError{reason: :extension_not_allowed}} =
+               Typster.render_pdf(~s(#read("data.json"\)), [files: files] ++ opts)
+    end
+  end
+
+  describe "package registry" do
+    setup do
+      dir = Path.join(System.tmp_dir!(), "typster-registry-#{System.unique_integer([:positive])}")
+      File.mkdir_p!(Path.join(dir, "registry/preview"))
+
+      toml = """
+      [package]
+      name = "mirrored"
+      version = "0.1.0"
+      entrypoint = "lib.typ"
+      """
+
+      :ok =
+        :erl_tar.create(
+          ~c"#{dir}/registry/preview/mirrored-0.1.0.tar.gz",
+          [{~c"typst.toml", toml}, {~c"lib.typ", "#let mirrored = [From the mirror]"}],
+          [:compressed]
+        )
+
+      on_exit(fn -> File.rm_rf!(dir) end)
+
+      %{
+        registry: "file://#{dir}/registry",
+        cache_dir: Path.join(dir, "cache")
+      }
+    end
+
+    test "downloads packages from a file:// registry", %{registry: url, cache_dir: cache_dir} do
+      template = ~s(#import "@preview/mirrored:0.1.0": mirrored\n#mirrored)
+      opts = [package_registry: url, package_cache_dir: cache_dir]
+
+      assert {:ok, pdf} = Typster.render_pdf(template, opts)
+      assert String.starts_with?(pdf, "%PDF")
+      assert File.exists?(Path.join(cache_dir, "preview/mirrored/0.1.0/lib.typ"))
+
+      # Once cached, the package is found offline
+      assert {:ok, _pdf} = Typster.render_pdf(template, [offline: true] ++ opts)
+    end
+
+    test "repairs incomplete packages in the cache", %{registry: url, cache_dir: cache_dir} do
+      # Left behind by an extraction that was interrupted
+      package_dir = Path.join(cache_dir, "preview/mirrored/0.1.0")
+      File.mkdir_p!(package_dir)
+      File.write!(Path.join(package_dir, "lib.typ"), "#let mirrored = [")
+
+      template = ~s(#import "@preview/mirrored:0.1.0": mirrored\n#mirrored)
+
+      assert {:ok, _pdf} =
+               Typster.render_pdf(template, package_registry: url, package_cache_dir: cache_dir)
+
+      assert File.exists?(Path.join(package_dir, "typst.toml"))
+      assert File.ls!(Path.dirname(package_dir)) == ["0.1.0"]
+    end
+
+    test "verifies pinned package archives", %{registry: url, cache_dir: cache_dir} do
+      "file://" <> registry = url
+      archive = File.read!(Path.join(registry, "preview/mirrored-0.1.0.tar.gz"))
+      digest = :crypto.hash(:sha256, archive) |> Base.encode16(case: :lower)
+      wrong = String.duplicate("0", 64)
+
+      template = ~s(#import "@preview/mirrored:0.1.0": mirrored\n#mirrored)
+      opts = [package_registry: url, package_cache_dir: cache_dir]
+
+      assert {:error, %Typster.CompileError{} = error} =
+               Typster.render_pdf(
+                 template,
+                 [package_hashes: %{"@preview/mirrored:0.1.0" => wrong}] ++ opts
+               )
+
+      assert Exception.message(error) =~ "expected SHA-256 #{wrong}, got #{digest}"
+      refute File.exists?(Path.join(cache_dir, "preview/mirrored/0.1.0"))
+
+      assert {:ok, _pdf} =
+               Typster.render_pdf(
+                 template,
+                 [package_hashes: %{"@preview/mirrored:0.1.0" => "sha256:" <> digest}] ++ opts
+               )
+    end
+
+    test "doesn't trust unverified pinned packages in the cache",
+         %{registry: url, cache_dir: cache_dir} do
+      "file://" <> registry = url
+      archive = File.read!(Path.join(registry, "preview/mirrored-0.1.0.tar.gz"))
+      digest = :crypto.hash(:sha256, archive) |> Base.encode16(case: :lower)
+      wrong = String.duplicate("0", 64)
+
+      # Installed without a pin, e.g. by the Typst CLI, and since modified
+      package_dir = Path.join(cache_dir, "preview/mirrored/0.1.0")
+      File.mkdir_p!(package_dir)
+
+      File.write!(Path.join(package_dir, "typst.toml"), """
+      [package]
+      name = "mirrored"
+      version = "0.1.0"
+      entrypoint = "lib.typ"
+      """)
+
+      File.write!(Path.join(package_dir, "lib.typ"), "#let mirrored = [Tampered]")
+
+      template = ~s(#import "@preview/mirrored:0.1.0": mirrored\n#mirrored)
+      opts = [package_registry: url, package_cache_dir: cache_dir]
+      pin = fn hash -> [package_hashes: %{"@preview/mirrored:0.1.0" => hash}] ++ opts end
+
+      assert {:error, %Typster.CompileError{} = error} = Typster.render_pdf(template, pin.(wrong))
+      assert Exception.message(error) =~ "expected SHA-256 #{wrong}, got #{digest}"
+
+      assert {:error, %Typster.CompileError{} = error} =
+               Typster.render_pdf(template, [offline: true] ++ pin.(digest))
+
+      assert Exception.message(error) =~ "isn't verified against its pinned digest"
+
+      assert {:ok, _pdf} = Typster.render_pdf(template, pin.(digest))
+      assert File.read!(Path.join(package_dir, "lib.typ")) == "#let mirrored = [From the mirror]"
+    end
+
+    test "returns an error for invalid package hashes" do
+      assert {:error, reason} =
+               Typster.Engine.new(package_hashes: %{"@preview/mirrored:0.1.0" => "md5:abc"})
+
+      assert reason =~ "Invalid SHA-256 digest 'md5:abc' for package @preview/mirrored:0.1.0"
+    end
+
+    test "returns an error for unsupported registry URLs" do
+      assert {:error, reason} = Typster.Engine.new(package_registry: "ftp://mirror.internal")
+      assert reason =~ "Invalid package registry URL 'ftp://mirror.internal'"
+    end
+  end
+
+  describe "package namespaces" do
+    setup do
+      dir =
+        Path.join(System.tmp_dir!(), "typster-namespaces-#{System.unique_integer([:positive])}")
+      File.mkdir_p!(Path.join(dir, "acme/letterhead/1.0.0"))
+      File.mkdir_p!(Path.join(dir, "archives"))
+
+      File.write!(Path.join(dir, "acme/letterhead/1.0.0/typst.toml"), """
+      [package]
+      name = "letterhead"
+      version = "1.0.0"
+      entrypoint = "lib.typ"
+      """)
+
+      File.write!(Path.join(dir, "acme/letterhead/1.0.0/lib.typ"), "#let head = [ACME Corp]")
+
+      toml = """
+      [package]
+      name = "footer"
+      version = "0.2.0"
+      entrypoint = "lib.typ"
+      """
+
+      :ok =
+        :erl_tar.create(
+          ~c"#{dir}/archives/footer-0.2.0.tar.gz",
+          [{~c"typst.toml", toml}, {~c"lib.typ", "#let foot = [Confidential]"}],
+          [:compressed]
+        )
+
+      on_exit(fn -> File.rm_rf!(dir) end)
+
+      %{dir: dir}
+    end
+
+    test "uses packages from a directory namespace", %{dir: dir} do
+      template = ~s(#import "@acme/letterhead:1.0.0": head\n#head)
+      namespaces = %{"acme" => {:directory, Path.join(dir, "acme")}}
+
+      assert {:ok, pdf} = Typster.render_pdf(template, package_namespaces: namespaces)
+      assert String.starts_with?(pdf, "%PDF")
+
+      assert {:error, %Typster.CompileError{} = error} =
+               Typster.render_pdf(~s(#import "@acme/letterhead:2.0.0": head),
+                 package_namespaces: namespaces
+               )
+
+      assert Exception.message(error) =~
+               "package not found (searched for @acme/letterhead:2.0.0)"
+    end
+
+    test "extracts packages from an archives namespace", %{dir: dir} do
+      template = ~s(#import "@internal/footer:0.2.0": foot\n#foot)
+
+      opts = [
+        package_namespaces: %{internal: {:archives, Path.join(dir, "archives")}},
+        package_cache_dir: Path.join(dir, "cache")
+      ]
+
+      assert {:ok, _pdf} = Typster.render_pdf(template, opts)
+      assert File.exists?(Path.join(dir, "cache/internal/footer/0.2.0/lib.typ"))
+    end
+  end
+
+  # A package directory holding `@local/greeting:1.0.0`, used through `:package_paths`.
+  defp local_package(_context) do
+    dir = Path.join(System.tmp_dir!(), "typster-local-#{System.unique_integer([:positive])}")
+    package_dir = Path.join(dir, "local/greeting/1.0.0")
+    File.mkdir_p!(package_dir)
+
+    File.write!(Path.join(package_dir, "typst.toml"), """
+    [package]
+    name = "greeting"
+    version = "1.0.0"
+    entrypoint = "lib.typ"
+    """)
+
+    File.write!(Path.join(package_dir, "lib.typ"), ~s(#let greet(name\) = [Hello, #name!]))
+    on_exit(fn -> File.rm_rf!(dir) end)
+
+    %{package_paths: [dir]}
+  end
 end
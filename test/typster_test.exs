@@ -56,9 +56,23 @@ defmodule TypsterTest do
 
     test "returns error for invalid template" do
       invalid_template = "#invalid syntax {"
-      assert {:error, reason} = Typster.render_pdf(invalid_template)
-      assert is_binary(reason)
-      assert reason =~ "Compilation failed"
+      assert {:error, %Typster.CompileError{} = reason} = Typster.render_pdf(invalid_template)
+      assert reason.message =~ "Compilation failed"
+      assert [%Typster.Diagnostic{severity: :error} | _] = reason.diagnostics
+    end
+
+    test "returns diagnostics with the location of the error" do
+      template = "= Title\nHello #unknown_var"
+
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic]} = reason} =
+               Typster.render_pdf(template)
+
+      assert diagnostic.message == "unknown variable: unknown_var"
+      assert diagnostic.path == "main.typ"
+      assert {diagnostic.line, diagnostic.column} == {2, 8}
+      assert {diagnostic.end_line, diagnostic.end_column} == {2, 19}
+      assert diagnostic.snippet == "unknown_var"
+      assert reason.message =~ "main.typ:2:8: error: unknown variable: unknown_var"
     end
 
     test "accepts atom keys in variables" do
@@ -421,6 +435,42 @@ defmodule TypsterTest do
       assert is_binary(pdf)
     end
 
+    test "preserves float values exactly" do
+      template = """
+      #assert.eq(big, 1.0e300)
+      #assert.eq(small, 1.5e-10)
+      #assert.eq(rows.at(1).at("price"), 19.99)
+      = Floats
+      """
+
+      variables = %{
+        big: 1.0e300,
+        small: 1.5e-10,
+        rows: [%{price: 9.99}, %{price: 19.99}]
+      }
+
+      assert {:ok, pdf} = Typster.render_pdf(template, variables: variables)
+      assert is_binary(pdf)
+    end
+
+    test "handles large datasets" do
+      template = """
+      #assert.eq(rows.len(), 50000)
+      #assert.eq(rows.last().at("name"), "Row 50000")
+      = Rows: #rows.len()
+      """
+
+      rows = for i <- 1..50_000, do: %{id: i, name: "Row #{i}", amount: i * 1.5}
+
+      assert {:ok, pdf} = Typster.render_pdf(template, variables: %{rows: rows})
+      assert is_binary(pdf)
+    end
+
+    test "returns error for variable names that are not valid identifiers" do
+      assert {:error, reason} = Typster.render_pdf("= Test", variables: %{"2025-01" => 1})
+      assert reason =~ "'2025-01' is not a valid Typst identifier"
+    end
+
     test "handles dictionary keys with numeric prefixes" do
       template = """
       = Data
@@ -458,7 +508,28 @@ defmodule TypsterTest do
       assert {:error, errors} = Typster.check(invalid_template)
       assert is_list(errors)
       refute Enum.empty?(errors)
-      assert Enum.all?(errors, &is_binary/1)
+      assert Enum.all?(errors, &match?(%Typster.Diagnostic{severity: :error}, &1))
+    end
+
+    test "reports line numbers relative to the template regardless of injected code" do
+      template = "= Title\nHello #unknown_var"
+      variables = %{a: 1, b: "two", c: [1, 2, 3]}
+
+      assert {:error, [diagnostic]} = Typster.check(template, variables: variables)
+      assert {diagnostic.line, diagnostic.column} == {2, 8}
+
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic]}} =
+               Typster.render_pdf(template, variables: variables, metadata: %{title: "Test"})
+
+      assert {diagnostic.line, diagnostic.column} == {2, 8}
+    end
+
+    test "returns diagnostics with trace for errors inside functions" do
+      template = "#let f(x) = x + \"a\"\n#f(1)"
+
+      assert {:error, [diagnostic]} = Typster.check(template)
+      assert diagnostic.line == 1
+      assert [%{message: "while calling `f`", line: 2}] = diagnostic.trace
     end
 
     test "returns error for unclosed brackets" do
@@ -510,6 +581,541 @@ defmodule TypsterTest do
     end
   end
 
+  describe "sys_inputs option" do
+    test "exposes variables through sys.inputs" do
+      template = """
+      = Report for #sys.inputs.year
+      #assert.eq(sys.inputs.customer.name, "Acme")
+      """
+
+      variables = %{year: "2025", customer: %{name: "Acme"}}
+
+      assert {:ok, pdf} = Typster.render_pdf(template, variables: variables, sys_inputs: true)
+      assert is_binary(pdf)
+    end
+
+    test "does not bind variables as top-level names" do
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic]}} =
+               Typster.render_pdf("= #year", variables: %{year: "2025"}, sys_inputs: true)
+
+      assert diagnostic.message == "unknown variable: year"
+    end
+
+    test "allows keys that are not valid identifiers" do
+      template = ~S|#assert.eq(sys.inputs.at("2025-01"), 42)|
+
+      assert :ok = Typster.check(template, variables: %{"2025-01" => 42}, sys_inputs: true)
+    end
+
+    test "sys.inputs is empty by default" do
+      template = "#assert.eq(sys.inputs, (:))"
+      assert :ok = Typster.check(template, variables: %{year: "2025"})
+    end
+  end
+
+  describe "today option" do
+    test "overrides datetime.today() with a Date" do
+      template = """
+      #assert.eq(datetime.today(), datetime(year: 2024, month: 2, day: 29))
+      #assert.eq(datetime.today(offset: 12), datetime(year: 2024, month: 2, day: 29))
+      """
+
+      assert :ok = Typster.check(template, today: ~D[2024-02-29])
+    end
+
+    test "takes the UTC offset of a DateTime into account" do
+      {:ok, now, _} = DateTime.from_iso8601("2024-03-01T02:00:00+03:00")
+
+      template = """
+      #assert.eq(datetime.today(offset: 0), datetime(year: 2024, month: 2, day: 29))
+      #assert.eq(datetime.today(offset: 1), datetime(year: 2024, month: 3, day: 1))
+      """
+
+      assert :ok = Typster.check(template, today: now)
+      assert :ok = Typster.check(template, today: ~N[2024-02-29 23:00:00])
+    end
+
+    test "makes output reproducible" do
+      template = "#datetime.today().display()"
+      opts = [today: ~D[2020-01-01]]
+
+      assert {:ok, [svg]} = Typster.render_svg(template, opts)
+      assert {:ok, [^svg]} = Typster.render_svg(template, opts)
+    end
+
+    test "returns an error for other values" do
+      assert {:error, reason} = Typster.render_pdf("= Test", today: "2024-02-29")
+      assert reason =~ "Expected a Date, DateTime or NaiveDateTime for today, got binary"
+
+      assert {:error, reason} = Typster.render_pdf("= Test", today: %{year: 2024, month: 2})
+      assert reason =~ "Expected a Date, DateTime or NaiveDateTime for today, got map"
+
+      assert {:error, reason} = Typster.render_pdf("= Test", today: ~T[12:00:00])
+      assert reason =~ "Expected a Date, DateTime or NaiveDateTime for today, got Time"
+    end
+  end
+
+  describe "metadata option" do
+    test "takes precedence over the metadata set by the template" do
+      template = """
+      #set document(title: "Template", author: "Ann")
+      = Report
+      """
+
+      assert {:ok, pdf} = Typster.render_pdf(template, metadata: %{title: "Option"})
+      assert pdf =~ "/Title(Option)"
+      assert pdf =~ "/Author(Ann)"
+    end
+
+    test "accepts lists of authors and keywords" do
+      metadata = %{author: ["Ann", "Bob"], keywords: ["invoice", "2025"]}
+
+      assert {:ok, pdf} = Typster.render_pdf("= Report", metadata: metadata)
+      assert pdf =~ "/Author(Ann, Bob)"
+      assert pdf =~ "/Keywords(invoice, 2025)"
+    end
+
+    test "sets the language and creator" do
+      metadata = %{language: "de-AT", creator: "MyApp"}
+
+      assert {:ok, pdf} = Typster.render_pdf("= Bericht", metadata: metadata)
+      assert pdf =~ "/Lang(de-AT)"
+      assert pdf =~ "/Creator(MyApp)"
+    end
+
+    test "is visible to the template through document" do
+      template = """
+      #context assert.eq(document.title.text, "Quarterly")
+      #context assert.eq(document.author, ("Ann", "Bob"))
+      #title()
+      """
+
+      metadata = %{title: "Quarterly", author: ["Ann", "Bob"]}
+
+      assert {:ok, pdf} = Typster.render_pdf(template, metadata: metadata)
+      assert pdf =~ "/Title(Quarterly)"
+      assert {:ok, [_svg]} = Typster.render_svg(template, metadata: metadata)
+    end
+
+    test "adds custom XMP properties" do
+      xmp = """
+      <rdf:Description rdf:about="" xmlns:fx="urn:factur-x">
+        <fx:DocumentType>INVOICE</fx:DocumentType>
+      </rdf:Description>
+      """
+
+      metadata = %{title: "Invoice", date: ~D[2025-01-31], xmp: xmp}
+      opts = [metadata: metadata, pdf_standards: [:a_3b]]
+
+      assert {:ok, pdf} = Typster.render_pdf("= Invoice", opts)
+      assert pdf =~ "<fx:DocumentType>INVOICE</fx:DocumentType>"
+      assert pdf =~ "<dc:title>"
+      assert pdf =~ ~r/%%EOF$/
+
+      assert {:error, reason} =
+               Typster.render_pdf("= Invoice", metadata: %{xmp: "<x:xmpmeta></x:xmpmeta>"})
+
+      assert reason =~ "Metadata 'xmp' must be rdf:Description elements"
+    end
+
+    test "keeps the template's values for keys set to nil" do
+      template = """
+      #set document(title: "Template", author: "Ann")
+      = Report
+      """
+
+      assert {:ok, pdf} = Typster.render_pdf(template, metadata: %{title: nil, author: "Bob"})
+      assert pdf =~ "/Title(Template)"
+      assert pdf =~ "/Author(Bob)"
+    end
+
+    test "returns an error for values that aren't strings" do
+      assert {:error, reason} = Typster.render_pdf("= Report", metadata: %{title: true})
+      assert reason =~ "Metadata 'title' must be a string"
+
+      assert {:error, reason} = Typster.render_pdf("= Report", metadata: %{author: :ann})
+      assert reason =~ "Metadata 'author' must be a string or a list of strings"
+    end
+
+    test "returns an error for unknown keys and invalid languages" do
+      assert {:error, reason} = Typster.render_pdf("= Report", metadata: %{subject: "Test"})
+      assert reason =~ "Unknown metadata key 'subject'"
+
+      assert {:error, reason} = Typster.render_pdf("= Report", metadata: %{language: "english"})
+      assert reason =~ "Invalid metadata language 'english'"
+    end
+  end
+
+  describe "pdf_standards option" do
+    test "renders PDF/A-3b documents" do
+      opts = [pdf_standards: [:a_3b], metadata: %{date: ~D[2025-01-31]}]
+
+      assert {:ok, pdf} = Typster.render_pdf("= Invoice", opts)
+      assert pdf =~ "<pdfaid:part>3</pdfaid:part>"
+      assert pdf =~ "<pdfaid:conformance>B</pdfaid:conformance>"
+    end
+
+    test "returns the validation errors as diagnostics" do
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic]}} =
+               Typster.render_pdf("= Report", pdf_standards: ["ua-1"])
+
+      assert diagnostic.severity == :error
+      assert diagnostic.message == "PDF/UA-1 error: missing document title"
+      assert diagnostic.hints != []
+    end
+
+    test "returns a diagnostic for incompatible standards" do
+      assert {:error, %Typster.CompileError{diagnostics: [diagnostic]}} =
+               Typster.render_pdf("= Report", pdf_standards: ["a-1b", "2.0"])
+
+      assert diagnostic.message == "PDF 2.0 is not compatible with PDF/A-1b"
+    end
+
+    test "returns an error for unknown standards" do
+      assert {:error, reason} = Typster.render_pdf("= Report", pdf_standards: [:a_9z])
+      assert reason =~ "Unknown PDF standard 'a_9z'"
+    end
+  end
+
+  describe "reproducible PDFs" do
+    test "renders identical inputs to identical bytes" do
+      opts = [metadata: %{title: "Invoice"}, creation_timestamp: ~U[2025-01-31 12:00:00Z]]
+
+      assert {:ok, pdf} = Typster.render_pdf("= Invoice", opts)
+      assert {:ok, ^pdf} = Typster.render_pdf("= Invoice", opts)
+    end
+
+    test "document_id keeps the identifier stable across revisions" do
+      id = fn pdf -> Regex.run(~r{/ID\[\((.+?)\)}, pdf, capture: :all_but_first) end
+
+      assert {:ok, first} = Typster.render_pdf("= Draft", document_id: "INV-1")
+      assert {:ok, second} = Typster.render_pdf("= Final", document_id: "INV-1")
+      assert {:ok, other} = Typster.render_pdf("= Final", document_id: "INV-2")
+
+      assert id.(first) == id.(second)
+      assert id.(first) != id.(other)
+    end
+
+    test "creation_timestamp sets the creation date" do
+      # DateTime.from_iso8601/1 would shift the time to UTC
+      timestamp = %DateTime{
+        ~U[2024-02-29 23:30:00Z]
+        | time_zone: "Etc/GMT-2",
+          zone_abbr: "+02",
+          utc_offset: 7200
+      }
+
+      assert {:ok, pdf} = Typster.render_pdf("= Report", creation_timestamp: timestamp)
+      assert pdf =~ "/CreationDate(D:20240229233000+02'00)"
+
+      naive = ~N[2024-02-29 23:30:00]
+      assert {:ok, pdf} = Typster.render_pdf("= Report", creation_timestamp: naive)
+      assert pdf =~ "/CreationDate(D:20240229233000"
+
+      assert {:ok, pdf} = Typster.render_pdf("= Report")
+      refute pdf =~ "/CreationDate"
+    end
+
+    test "the date metadata takes precedence over creation_timestamp" do
+      opts = [metadata: %{date: "2020-01-01"}, creation_timestamp: ~D[2024-02-29]]
+
+      assert {:ok, pdf} = Typster.render_pdf("= Report", opts)
+      assert pdf =~ "/CreationDate(D:20200101"
+    end
+
+    test "returns an error for invalid timestamps" do
+      assert {:error, reason} = Typster.render_pdf("= Report", creation_timestamp: "now")
+
+      assert reason =~
+               "Expected a Date, DateTime or NaiveDateTime for creation_timestamp, got binary"
+    end
+  end
+
+  describe "pages option" do
+    @four_pages "= One\n#pagebreak()\n= Two\n#pagebreak()\n= Three\n#pagebreak()\n= Four"
+
+    test "renders only the selected SVG and PNG pages" do
+      assert {:ok, [one, three, four]} = Typster.render_svg(@four_pages, pages: [1, 3..4])
+      assert {:ok, [^one]} = Typster.render_svg(@four_pages, pages: 1)
+      assert {:ok, [^three, ^four]} = Typster.render_svg(@four_pages, pages: 3..4)
+
+      assert {:ok, [thumbnail]} = Typster.render_png(@four_pages, pages: [1])
+      assert binary_part(thumbnail, 0, 8) == <<137, 80, 78, 71, 13, 10, 26, 10>>
+    end
+
+    test "accepts descending ranges" do
+      assert {:ok, [three, four]} = Typster.render_svg(@four_pages, pages: 3..4)
+      assert {:ok, [^three, ^four]} = Typster.render_svg(@four_pages, pages: 4..3//-1)
+    end
+
+    test "selects every page of a range with a step" do
+      assert {:ok, [one, _two, three, _four]} = Typster.render_svg(@four_pages)
+      assert {:ok, [^one, ^three]} = Typster.render_svg(@four_pages, pages: 1..4//2)
+      assert {:ok, [^one, ^three]} = Typster.render_svg(@four_pages, pages: 3..1//-2)
+    end
+
+    test "exports only the selected PDF pages" do
+      assert {:ok, pdf} = Typster.render_pdf(@four_pages, pages: [1, 3..4])
+      assert pdf =~ "/Count 3"
+    end
+
+    test "returns an error when no page is selected" do
+      assert {:error, reason} = Typster.render_svg(@four_pages, pages: [9])
+      assert reason =~ "The pages option selects none of the document's 4 pages"
+    end
+
+    test "returns an error for empty ranges" do
+      assert {:error, reason} = Typster.render_pdf(@four_pages, pages: [1, 4..3//1])
+      assert reason == "Empty page range 4..3//1 selects no pages"
+
+      assert {:error, reason} = Typster.render_svg(@four_pages, pages: 3..1//2)
+      assert reason == "Empty page range 3..1//2 selects no pages"
+    end
+
+    test "returns an error for invalid pages" do
+      assert {:error, reason} = Typster.render_pdf(@four_pages, pages: [0])
+      assert reason =~ "Invalid page range 0..0"
+
+      assert {:error, reason} = Typster.render_png(@four_pages, pages: ["1"])
+      assert reason =~ "Invalid pages entry of type binary"
+    end
+  end
+
+  describe "warnings" do
+    @template_with_warning """
+    #set text(font: "No Such Font")
+    = Hello World
+    """
+
+    test "render_pdf returns warnings when requested" do
+      assert {:ok, pdf, [warning]} =
+               Typster.render_pdf(@template_with_warning, with_warnings: true)
+
+      assert String.starts_with?(pdf, "%PDF")
+      assert %Typster.Diagnostic{severity: :warning, line: 1} = warning
+      assert warning.message =~ "unknown font family"
+    end
+
+    test "render_pdf omits warnings by default" do
+      assert {:ok, pdf} = Typster.render_pdf(@template_with_warning)
+      assert is_binary(pdf)
+    end
+
+    test "render_svg and render_png return warnings when requested" do
+      assert {:ok, [_svg], [_warning]} =
+               Typster.render_svg(@template_with_warning, with_warnings: true)
+
+      assert {:ok, [_png], [_warning]} =
+               Typster.render_png(@template_with_warning, with_warnings: true)
+    end
+
+    test "returns an empty list when there are no warnings" do
+      assert {:ok, _pdf, []} = Typster.render_pdf(@simple_template, with_warnings: true)
+    end
+
+    test "bang functions return output and warnings when requested" do
+      assert {pdf, [_warning]} = Typster.render_pdf!(@template_with_warning, with_warnings: true)
+      assert is_binary(pdf)
+    end
+
+    test "check returns warnings when requested" do
+      assert :ok = Typster.check(@template_with_warning)
+
+      assert {:ok, [%Typster.Diagnostic{severity: :warning}]} =
+               Typster.check(@template_with_warning, with_warnings: true)
+    end
+
+    test "check includes warnings alongside errors" do
+      template = "#show page: it => it\n#unknown_var"
+
+      assert {:error, diagnostics} = Typster.check(template)
+      assert Enum.map(diagnostics, & &1.severity) == [:error, :warning]
+    end
+
+    test "render_to_file ignores the with_warnings option" do
+      on_exit(fn -> File.rm("test_output.pdf") end)
+
+      assert :ok =
+               Typster.render_to_file(@template_with_warning, "test_output.pdf",
+                 with_warnings: true
+               )
+    end
+  end
+
+  describe "files option" do
+    test "serves imports, images and data files from memory" do
+      {:ok, [logo]} = Typster.render_png(@simple_template)
+
+      template = """
+      #import "lib/header.typ": header
+      #header
+      #image("assets/logo.png", width: 2cm)
+      #let rows = csv("data.csv")
+      #assert.eq(rows.len(), 2)
+      """
+
+      files = %{
+        "lib/header.typ" => "#let header = [= Quarterly Report]",
+        "assets/logo.png" => logo,
+        "data.csv" => "name,amount\nAcme,100\n"
+      }
+
+      assert {:ok, "%PDF" <> _} = Typster.render_pdf(template, files: files)
+    end
+
+    test "resolves relative paths from files in memory" do
+      files = %{
+        "lib/header.typ" => ~s(#let title = read("title.txt"\)),
+        "lib/title.txt" => "Report"
+      }
+
+      template = ~s(#import "lib/header.typ": title\n#assert.eq(title, "Report"\))
+
+      assert :ok = Typster.check(template, files: files)
+    end
+
+    test "returns an error for paths escaping the project root" do
+      assert {:error, reason} =
+               Typster.render_pdf("= Test", files: %{"../secret.typ" => "#let x = 1"})
+
+      assert reason =~ "Invalid file path '../secret.typ'"
+    end
+  end
+
+  describe "sandbox option" do
+    @describetag :tmp_dir
+
+    test "memory mode only serves files passed in memory", %{tmp_dir: tmp_dir} do
+      File.write!(Path.join(tmp_dir, "secret.txt"), "secret")
+      opts = [root_path: tmp_dir, sandbox: :memory, files: %{"data.txt" => "data"}]
+
+      assert :ok = Typster.check(~s(#assert.eq(read("data.txt"\), "data"\)), opts)
+
+      assert {:error, %Typster.AccessError{} = error} =
+               Typster.render_pdf(~s(#read("secret.txt"\)), opts)
+
+      assert error.path == "/secret.txt"
+      assert error.reason == :disk_access_disabled
+      assert error.message ==
+               "access denied: /secret.txt is not in memory and disk access is disabled"
+      assert [%Typster.Diagnostic{severity: :error} | _] = error.diagnostics
+    end
+
+    test "strict mode rejects symlinks escaping the root path", %{tmp_dir: tmp_dir} do
+      root = Path.join(tmp_dir, "root")
+      File.mkdir_p!(root)
+      File.write!(Path.join(tmp_dir, "secret.txt"), "secret")
+      File.write!(Path.join(root, "data.txt"), "data")
+      File.ln_s!(Path.join(tmp_dir, "secret.txt"), Path.join(root, "link.txt"))
+
+      opts = [root_path: root, sandbox: :strict]
+
+      assert :ok = Typster.check(~s(#assert.eq(read("data.txt"\), "data"\)), opts)
+
+      assert {:error, %Typster.AccessError{path: "/link.txt", reason: :outside_root}} =
+               Typster.render_svg(~s(#read("link.txt"\)), opts)
+
+      assert_raise Typster.AccessError, ~r/outside the project root/, fn ->
+        Typster.render_pdf!(~s(#read("link.txt"\)), opts)
+      end
+    end
+
+    test "check/2 reports denied reads as diagnostics", %{tmp_dir: tmp_dir} do
+      assert {:error, [diagnostic | _]} =
+               Typster.check(~s(#read("secret.txt"\)), root_path: tmp_dir, sandbox: :memory)
+
+      assert diagnostic.message =~ "access denied: /secret.txt"
+    end
+
+    test "returns an error for a missing root path in strict mode", %{tmp_dir: tmp_dir} do
+      assert {:error, reason} =
+               Typster.render_pdf("= Test",
+                 root_path: Path.join(tmp_dir, "missing"),
+                 sandbox: :strict
+               )
+
+      assert reason =~ "can't be sandboxed"
+    end
+  end
+
+  describe "file limits" do
+    @files %{"data.json" => ~s({"a": 1}), "notes.txt" => "notes", "big.txt" => "0123456789"}
+
+    test "only loads allowed file extensions" do
+      opts = [files: @files, allowed_extensions: ["typ", ".JSON"]]
+
+      assert :ok = Typster.check(~s(#assert.eq(json("data.json"\).a, 1\)), opts)
+
+      assert {:error, %Typster.AccessError{path: "/notes.txt", reason: :extension_not_allowed}} =
+               Typster.render_pdf(~s(#read("notes.txt"\)), opts)
+    end
+
+    test "limits the size of each file" do
+      opts = [files: @files, max_file_size: 9]
+
+      assert :ok = Typster.check(~s(#read("notes.txt"\)), opts)
+
+      assert {:error, %Typster.AccessError{path: "/big.txt", reason: :file_too_large}} =
+               Typster.render_pdf(~s(#read("big.txt"\)), opts)
+    end
+
+    test "limits the total size of loaded files" do
+      opts = [files: @files, max_total_size: 12]
+
+      assert :ok = Typster.check(~s(#read("notes.txt"\)\n#read("notes.txt"\)), opts)
+
+      assert {:error, %Typster.AccessError{path: "/big.txt", reason: :total_size_exceeded}} =
+               Typster.render_png(~s(#read("notes.txt"\)\n#read("big.txt"\)), opts)
+    end
+
+    @tag :tmp_dir
+    test "refuses large files on disk", %{tmp_dir: tmp_dir} do
+      File.write!(Path.join(tmp_dir, "large.txt"), String.duplicate("a", 1024))
+
+      opts = [root_path: tmp_dir, max_file_size: 1000]
+
+      assert {:error, %Typster.AccessError{reason: :file_too_large}} =
+               Typster.render_pdf(~s(#read("large.txt"\)), opts)
+    end
+  end
+
+  describe "font options" do
+    test "embedded fonts are available when system fonts are ignored" do
+      template = "#set text(font: \"Libertinus Serif\")\nHello"
+
+      assert {:ok, _pdf, []} =
+               Typster.render_pdf(template, ignore_system_fonts: true, with_warnings: true)
+    end
+
+    test "returns an error for a font path that is not a directory" do
+      assert {:error, reason} =
+               Typster.render_pdf(@simple_template, font_paths: ["/nonexistent/fonts"])
+
+      assert reason =~ "Font path '/nonexistent/fonts' is not a directory"
+    end
+
+    test "returns an error for font binaries that are not fonts" do
+      assert {:error, reason} = Typster.render_pdf(@simple_template, fonts: ["not a font"])
+      assert reason =~ "Font at index 0 is not a valid TTF, OTF or TTC font"
+    end
+
+    test "engines accept font options" do
+      assert {:ok, engine} = Typster.Engine.new(ignore_system_fonts: true)
+      assert {:ok, _pdf} = Typster.render_pdf(@simple_template, engine: engine)
+
+      assert {:error, reason} = Typster.Engine.new(font_paths: ["/nonexistent/fonts"])
+      assert reason =~ "is not a directory"
+    end
+  end
+
+  describe "refresh_fonts/0" do
+    test "rescans fonts without affecting renders" do
+      assert {:ok, _pdf} = Typster.render_pdf(@simple_template)
+      assert :ok = Typster.refresh_fonts()
+      assert {:ok, _pdf} = Typster.render_pdf(@simple_template)
+    end
+  end
+
   describe "check!/3" do
     test "returns :ok for valid template" do
       assert :ok = Typster.check!(@simple_template)
@@ -529,6 +1135,7 @@ defmodule TypsterTest do
 
       assert is_binary(error.message)
       assert String.length(error.message) > 0
+      assert [%Typster.Diagnostic{} | _] = error.diagnostics
     end
 
     test "works with variables" do